@@ -11,15 +11,24 @@ pub mod weapon_type;
 
 pub mod position;
 pub mod prelude;
+#[cfg(feature = "serde")]
+mod serde_impl;
 
 #[allow(clippy::all)]
 mod bindings {
     use num_derive::FromPrimitive;
-    // include!("bindings.rs");
+    #[cfg(not(feature = "generate-bindings"))]
+    include!("bindings.rs");
+    #[cfg(feature = "generate-bindings")]
     include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 }
 pub use bindings::*;
 
+/// Version of the client protocol the bindings were generated for, taken from
+/// `BWAPI::CLIENT_VERSION`. A server with a different version uses a different `BWAPI_GameData`
+/// layout.
+pub const CLIENT_VERSION: i32 = BWAPI_CLIENT_VERSION;
+
 pub trait TypeFrom {
     fn new(i: i32) -> Self;
 }
@@ -5,6 +5,7 @@ use derive_more::{Add, AddAssign, Display, From, Sub, SubAssign};
 #[derive(
     Default, Debug, Display, Copy, Clone, Eq, PartialEq, Add, Sub, AddAssign, SubAssign, From, Hash,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[display("Position<{}> ({}, {})", N, x, y)]
 pub struct ScaledPosition<const N: i32> {
     pub x: i32,
@@ -16,6 +17,7 @@ pub type WalkPosition = ScaledPosition<8>;
 pub type TilePosition = ScaledPosition<32>;
 
 #[derive(Debug, Copy, Clone, PartialEq, Add, Sub, AddAssign, SubAssign, From)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vector2D {
     pub x: f64,
     pub y: f64,
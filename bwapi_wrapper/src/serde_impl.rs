@@ -0,0 +1,167 @@
+use crate::prelude::*;
+use crate::{BWAPI_BulletTypes_Enum_Enum, BWAPI_UnitCommandType};
+use num_traits::FromPrimitive;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Enums are (de)serialized by their names, ie. `"Terran_Marine"`. Unlike the ids, the names
+/// don't change between BWAPI versions. `$last` is the variant with the highest id.
+macro_rules! named_enum {
+    ($($type:ty: $last:ident),* $(,)?) => {$(
+        impl Serialize for $type {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(&format_args!("{:?}", self))
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $type {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                static BY_NAME: OnceLock<HashMap<String, $type>> = OnceLock::new();
+                let name = String::deserialize(deserializer)?;
+                BY_NAME
+                    .get_or_init(|| {
+                        (0..=<$type>::$last as i32)
+                            .filter_map(<$type>::from_i32)
+                            .map(|value| (format!("{:?}", value), value))
+                            .collect()
+                    })
+                    .get(&name)
+                    .copied()
+                    .ok_or_else(|| {
+                        D::Error::custom(format!("unknown {} `{}`", stringify!($type), name))
+                    })
+            }
+        }
+    )*};
+}
+
+named_enum!(
+    UnitType: MAX,
+    TechType: MAX,
+    UpgradeType: MAX,
+    WeaponType: MAX,
+    Race: MAX,
+    Order: MAX,
+    UnitCommandType: MAX,
+    BWAPI_BulletTypes_Enum_Enum: Unknown,
+);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "UnitCommand")]
+struct UnitCommandDef {
+    #[serde(rename = "type")]
+    type_: UnitCommandType,
+    unit: i32,
+    target: i32,
+    x: i32,
+    y: i32,
+    extra: i32,
+}
+
+impl Serialize for UnitCommand {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        UnitCommandDef {
+            type_: self.get_type(),
+            unit: self.unitIndex,
+            target: self.targetIndex,
+            x: self.x,
+            y: self.y,
+            extra: self.extra,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for UnitCommand {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let command = UnitCommandDef::deserialize(deserializer)?;
+        Ok(UnitCommand {
+            type_: BWAPI_UnitCommandType {
+                _base: command.type_ as u32,
+            },
+            unitIndex: command.unit,
+            targetIndex: command.target,
+            x: command.x,
+            y: command.y,
+            extra: command.extra,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_test::{Token, assert_de_tokens_error, assert_ser_tokens, assert_tokens};
+
+    /// `UnitCommand` doesn't implement `PartialEq`
+    #[derive(Debug, Deserialize)]
+    #[serde(transparent)]
+    struct Command(UnitCommand);
+
+    impl PartialEq for Command {
+        fn eq(&self, other: &Self) -> bool {
+            let (a, b) = (self.0, other.0);
+            (a.type_._base, a.unitIndex, a.targetIndex, a.x, a.y, a.extra)
+                == (b.type_._base, b.unitIndex, b.targetIndex, b.x, b.y, b.extra)
+        }
+    }
+
+    #[test]
+    fn should_use_names_for_enums() {
+        assert_tokens(&UnitType::Terran_Marine, &[Token::Str("Terran_Marine")]);
+        assert_tokens(&TechType::Stim_Packs, &[Token::Str("Stim_Packs")]);
+        assert_tokens(&Race::Zerg, &[Token::Str("Zerg")]);
+        assert_de_tokens_error::<Order>(&[Token::Str("Dance")], "unknown Order `Dance`");
+    }
+
+    #[test]
+    fn should_serialize_positions_and_commands() {
+        assert_tokens(
+            &TilePosition { x: 3, y: 4 },
+            &[
+                Token::Struct {
+                    name: "ScaledPosition",
+                    len: 2,
+                },
+                Token::Str("x"),
+                Token::I32(3),
+                Token::Str("y"),
+                Token::I32(4),
+                Token::StructEnd,
+            ],
+        );
+        let command = UnitCommand {
+            type_: BWAPI_UnitCommandType {
+                _base: UnitCommandType::Train as u32,
+            },
+            unitIndex: 5,
+            targetIndex: -1,
+            x: 0,
+            y: 0,
+            extra: UnitType::Terran_SCV as i32,
+        };
+        let mut tokens = vec![
+            Token::Struct {
+                name: "UnitCommand",
+                len: 6,
+            },
+            Token::Str("type"),
+            Token::Str("Train"),
+        ];
+        let fields = [
+            ("unit", 5),
+            ("target", -1),
+            ("x", 0),
+            ("y", 0),
+            ("extra", 7),
+        ];
+        for (field, value) in fields {
+            tokens.extend([Token::Str(field), Token::I32(value)]);
+        }
+        tokens.push(Token::StructEnd);
+        assert_ser_tokens(&command, &tokens);
+        serde_test::assert_de_tokens(&Command(command), &tokens);
+    }
+}
@@ -0,0 +1,13 @@
+#!/bin/sh
+# Regenerates src/bindings.rs from the BWAPI headers, which is used when building without the
+# `generate-bindings` feature. Needs clang and the `bwapi` submodule.
+set -e
+cd "$(dirname "$0")"
+out_dir=$(cargo build -p bwapi_wrapper --features generate-bindings --message-format=json |
+    sed -n 's/.*"package_id":"[^"]*bwapi_wrapper#[^"]*".*"out_dir":"\([^"]*\)".*/\1/p' |
+    tail -n 1)
+if [ -z "$out_dir" ]; then
+    echo "Could not find the generated bindings" >&2
+    exit 1
+fi
+cp "$out_dir/bindings.rs" src/bindings.rs
@@ -25,3 +25,387 @@ pub trait AiModule {
     fn on_unit_renegade(&mut self, _game: &Game, _unit: Unit) {}
     fn on_unit_show(&mut self, _game: &Game, _unit: Unit) {}
 }
+
+/// Forwards each callback to all modules of the tuple, in order.
+macro_rules! impl_ai_module_for_tuple {
+    ($(($($module:ident $index:tt),+))*) => {$(
+        impl<$($module: AiModule),+> AiModule for ($($module,)+) {
+            fn on_end(&mut self, game: &Game, winner: bool) {
+                $(self.$index.on_end(game, winner);)+
+            }
+
+            fn on_nuke_detect(&mut self, game: &Game, position: Position) {
+                $(self.$index.on_nuke_detect(game, position);)+
+            }
+
+            fn on_player_left(&mut self, game: &Game, player: Player) {
+                $(self.$index.on_player_left(game, player.clone());)+
+            }
+
+            fn on_receive_text(&mut self, game: &Game, player: Player, text: impl Borrow<str>) {
+                $(self.$index.on_receive_text(game, player.clone(), text.borrow());)+
+            }
+
+            fn on_save_game(&mut self, game: &Game, game_name: impl Borrow<str>) {
+                $(self.$index.on_save_game(game, game_name.borrow());)+
+            }
+
+            fn on_send_text(&mut self, game: &Game, text: impl Borrow<str>) {
+                $(self.$index.on_send_text(game, text.borrow());)+
+            }
+
+            fn on_start(&mut self, game: &Game) {
+                $(self.$index.on_start(game);)+
+            }
+
+            fn on_frame(&mut self, game: &Game) {
+                $(self.$index.on_frame(game);)+
+            }
+
+            fn on_unit_create(&mut self, game: &Game, unit: Unit) {
+                $(self.$index.on_unit_create(game, unit.clone());)+
+            }
+
+            fn on_unit_destroy(&mut self, game: &Game, unit: Unit) {
+                $(self.$index.on_unit_destroy(game, unit.clone());)+
+            }
+
+            fn on_unit_discover(&mut self, game: &Game, unit: Unit) {
+                $(self.$index.on_unit_discover(game, unit.clone());)+
+            }
+
+            fn on_unit_complete(&mut self, game: &Game, unit: Unit) {
+                $(self.$index.on_unit_complete(game, unit.clone());)+
+            }
+
+            fn on_unit_evade(&mut self, game: &Game, unit: Unit) {
+                $(self.$index.on_unit_evade(game, unit.clone());)+
+            }
+
+            fn on_unit_hide(&mut self, game: &Game, unit: Unit) {
+                $(self.$index.on_unit_hide(game, unit.clone());)+
+            }
+
+            fn on_unit_morph(&mut self, game: &Game, unit: Unit) {
+                $(self.$index.on_unit_morph(game, unit.clone());)+
+            }
+
+            fn on_unit_renegade(&mut self, game: &Game, unit: Unit) {
+                $(self.$index.on_unit_renegade(game, unit.clone());)+
+            }
+
+            fn on_unit_show(&mut self, game: &Game, unit: Unit) {
+                $(self.$index.on_unit_show(game, unit.clone());)+
+            }
+        }
+    )*}
+}
+
+impl_ai_module_for_tuple! {
+    (A 0, B 1)
+    (A 0, B 1, C 2)
+    (A 0, B 1, C 2, D 3)
+    (A 0, B 1, C 2, D 3, E 4)
+    (A 0, B 1, C 2, D 3, E 4, F 5)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7)
+}
+
+/// Object safe version of `AiModule`, to store different modules in a `ModuleList`.
+trait DynAiModule {
+    fn on_end(&mut self, game: &Game, winner: bool);
+    fn on_nuke_detect(&mut self, game: &Game, position: Position);
+    fn on_player_left(&mut self, game: &Game, player: Player);
+    fn on_receive_text(&mut self, game: &Game, player: Player, text: &str);
+    fn on_save_game(&mut self, game: &Game, game_name: &str);
+    fn on_send_text(&mut self, game: &Game, text: &str);
+    fn on_start(&mut self, game: &Game);
+    fn on_frame(&mut self, game: &Game);
+    fn on_unit_create(&mut self, game: &Game, unit: Unit);
+    fn on_unit_destroy(&mut self, game: &Game, unit: Unit);
+    fn on_unit_discover(&mut self, game: &Game, unit: Unit);
+    fn on_unit_complete(&mut self, game: &Game, unit: Unit);
+    fn on_unit_evade(&mut self, game: &Game, unit: Unit);
+    fn on_unit_hide(&mut self, game: &Game, unit: Unit);
+    fn on_unit_morph(&mut self, game: &Game, unit: Unit);
+    fn on_unit_renegade(&mut self, game: &Game, unit: Unit);
+    fn on_unit_show(&mut self, game: &Game, unit: Unit);
+}
+
+impl<M: AiModule> DynAiModule for M {
+    fn on_end(&mut self, game: &Game, winner: bool) {
+        AiModule::on_end(self, game, winner)
+    }
+
+    fn on_nuke_detect(&mut self, game: &Game, position: Position) {
+        AiModule::on_nuke_detect(self, game, position)
+    }
+
+    fn on_player_left(&mut self, game: &Game, player: Player) {
+        AiModule::on_player_left(self, game, player)
+    }
+
+    fn on_receive_text(&mut self, game: &Game, player: Player, text: &str) {
+        AiModule::on_receive_text(self, game, player, text)
+    }
+
+    fn on_save_game(&mut self, game: &Game, game_name: &str) {
+        AiModule::on_save_game(self, game, game_name)
+    }
+
+    fn on_send_text(&mut self, game: &Game, text: &str) {
+        AiModule::on_send_text(self, game, text)
+    }
+
+    fn on_start(&mut self, game: &Game) {
+        AiModule::on_start(self, game)
+    }
+
+    fn on_frame(&mut self, game: &Game) {
+        AiModule::on_frame(self, game)
+    }
+
+    fn on_unit_create(&mut self, game: &Game, unit: Unit) {
+        AiModule::on_unit_create(self, game, unit)
+    }
+
+    fn on_unit_destroy(&mut self, game: &Game, unit: Unit) {
+        AiModule::on_unit_destroy(self, game, unit)
+    }
+
+    fn on_unit_discover(&mut self, game: &Game, unit: Unit) {
+        AiModule::on_unit_discover(self, game, unit)
+    }
+
+    fn on_unit_complete(&mut self, game: &Game, unit: Unit) {
+        AiModule::on_unit_complete(self, game, unit)
+    }
+
+    fn on_unit_evade(&mut self, game: &Game, unit: Unit) {
+        AiModule::on_unit_evade(self, game, unit)
+    }
+
+    fn on_unit_hide(&mut self, game: &Game, unit: Unit) {
+        AiModule::on_unit_hide(self, game, unit)
+    }
+
+    fn on_unit_morph(&mut self, game: &Game, unit: Unit) {
+        AiModule::on_unit_morph(self, game, unit)
+    }
+
+    fn on_unit_renegade(&mut self, game: &Game, unit: Unit) {
+        AiModule::on_unit_renegade(self, game, unit)
+    }
+
+    fn on_unit_show(&mut self, game: &Game, unit: Unit) {
+        AiModule::on_unit_show(self, game, unit)
+    }
+}
+
+struct Entry {
+    name: String,
+    enabled: bool,
+    module: Box<dyn DynAiModule>,
+}
+
+/// Named modules which receive each callback in order. Modules can be disabled, which skips all
+/// their callbacks until they are enabled again.
+///
+/// For a fixed set of modules, a tuple like `(Economy, Army, Scouting)` is an `AiModule` as well.
+#[derive(Default)]
+pub struct ModuleList {
+    modules: Vec<Entry>,
+}
+
+impl ModuleList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a module, which will receive callbacks after all modules already added.
+    pub fn push(&mut self, name: impl Into<String>, module: impl AiModule + 'static) -> &mut Self {
+        self.insert(self.modules.len(), name, module)
+    }
+
+    /// Inserts a module at `index`, shifting all modules after it.
+    pub fn insert(
+        &mut self,
+        index: usize,
+        name: impl Into<String>,
+        module: impl AiModule + 'static,
+    ) -> &mut Self {
+        self.modules.insert(
+            index,
+            Entry {
+                name: name.into(),
+                enabled: true,
+                module: Box::new(module),
+            },
+        );
+        self
+    }
+
+    /// Moves the module `name` to `index`.
+    pub fn move_to(&mut self, name: &str, index: usize) {
+        if let Some(position) = self.position(name) {
+            let entry = self.modules.remove(position);
+            self.modules.insert(index.min(self.modules.len()), entry);
+        }
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        if let Some(position) = self.position(name) {
+            self.modules[position].enabled = enabled;
+        }
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.position(name)
+            .is_some_and(|position| self.modules[position].enabled)
+    }
+
+    /// The names of all modules, in the order they receive callbacks.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.modules.iter().map(|entry| entry.name.as_str())
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.modules.iter().position(|entry| entry.name == name)
+    }
+
+    fn for_each_enabled(&mut self, mut callback: impl FnMut(&mut dyn DynAiModule)) {
+        for entry in self.modules.iter_mut().filter(|entry| entry.enabled) {
+            callback(entry.module.as_mut());
+        }
+    }
+}
+
+impl AiModule for ModuleList {
+    fn on_end(&mut self, game: &Game, winner: bool) {
+        self.for_each_enabled(|module| module.on_end(game, winner));
+    }
+
+    fn on_nuke_detect(&mut self, game: &Game, position: Position) {
+        self.for_each_enabled(|module| module.on_nuke_detect(game, position));
+    }
+
+    fn on_player_left(&mut self, game: &Game, player: Player) {
+        self.for_each_enabled(|module| module.on_player_left(game, player.clone()));
+    }
+
+    fn on_receive_text(&mut self, game: &Game, player: Player, text: impl Borrow<str>) {
+        self.for_each_enabled(|module| module.on_receive_text(game, player.clone(), text.borrow()));
+    }
+
+    fn on_save_game(&mut self, game: &Game, game_name: impl Borrow<str>) {
+        self.for_each_enabled(|module| module.on_save_game(game, game_name.borrow()));
+    }
+
+    fn on_send_text(&mut self, game: &Game, text: impl Borrow<str>) {
+        self.for_each_enabled(|module| module.on_send_text(game, text.borrow()));
+    }
+
+    fn on_start(&mut self, game: &Game) {
+        self.for_each_enabled(|module| module.on_start(game));
+    }
+
+    fn on_frame(&mut self, game: &Game) {
+        self.for_each_enabled(|module| module.on_frame(game));
+    }
+
+    fn on_unit_create(&mut self, game: &Game, unit: Unit) {
+        self.for_each_enabled(|module| module.on_unit_create(game, unit.clone()));
+    }
+
+    fn on_unit_destroy(&mut self, game: &Game, unit: Unit) {
+        self.for_each_enabled(|module| module.on_unit_destroy(game, unit.clone()));
+    }
+
+    fn on_unit_discover(&mut self, game: &Game, unit: Unit) {
+        self.for_each_enabled(|module| module.on_unit_discover(game, unit.clone()));
+    }
+
+    fn on_unit_complete(&mut self, game: &Game, unit: Unit) {
+        self.for_each_enabled(|module| module.on_unit_complete(game, unit.clone()));
+    }
+
+    fn on_unit_evade(&mut self, game: &Game, unit: Unit) {
+        self.for_each_enabled(|module| module.on_unit_evade(game, unit.clone()));
+    }
+
+    fn on_unit_hide(&mut self, game: &Game, unit: Unit) {
+        self.for_each_enabled(|module| module.on_unit_hide(game, unit.clone()));
+    }
+
+    fn on_unit_morph(&mut self, game: &Game, unit: Unit) {
+        self.for_each_enabled(|module| module.on_unit_morph(game, unit.clone()));
+    }
+
+    fn on_unit_renegade(&mut self, game: &Game, unit: Unit) {
+        self.for_each_enabled(|module| module.on_unit_renegade(game, unit.clone()));
+    }
+
+    fn on_unit_show(&mut self, game: &Game, unit: Unit) {
+        self.for_each_enabled(|module| module.on_unit_show(game, unit.clone()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::Client;
+    use crate::transport::MemoryTransport;
+    use bwapi_wrapper::BWAPI_EventType_Enum;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Recorder(&'static str, Rc<RefCell<Vec<&'static str>>>);
+
+    impl AiModule for Recorder {
+        fn on_frame(&mut self, _game: &Game) {
+            self.1.borrow_mut().push(self.0);
+        }
+    }
+
+    fn play_frame(module: &mut impl AiModule) {
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        let data = client.transport_mut().data_mut();
+        data.eventCount = 1;
+        data.events[0].type_ = BWAPI_EventType_Enum::MatchFrame;
+        client.update(module).unwrap();
+    }
+
+    #[test]
+    fn should_call_tuple_modules_in_order() {
+        let calls = Rc::new(RefCell::new(vec![]));
+        let mut modules = (
+            Recorder("economy", calls.clone()),
+            Recorder("army", calls.clone()),
+        );
+
+        play_frame(&mut modules);
+
+        assert_eq!(calls.take(), ["economy", "army"]);
+    }
+
+    #[test]
+    fn should_skip_disabled_modules_and_respect_order() {
+        let calls = Rc::new(RefCell::new(vec![]));
+        let mut modules = ModuleList::new();
+        modules
+            .push("economy", Recorder("economy", calls.clone()))
+            .push("army", Recorder("army", calls.clone()))
+            .push("debug", Recorder("debug", calls.clone()));
+        modules.move_to("army", 0);
+        modules.set_enabled("debug", false);
+
+        play_frame(&mut modules);
+
+        assert_eq!(calls.take(), ["army", "economy"]);
+        assert!(!modules.is_enabled("debug"));
+        assert_eq!(
+            modules.names().collect::<Vec<_>>(),
+            ["army", "economy", "debug"]
+        );
+    }
+}
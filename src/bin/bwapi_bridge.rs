@@ -0,0 +1,28 @@
+//! Relays the BWAPI server on this host to bots on other hosts, see `rsbwapi::bridge`.
+//!
+//! Usage: `bwapi_bridge [listen address]`, only listens on the loopback interface by default.
+//! The relay has no authentication, so only listen on networks the bots are trusted on.
+use rsbwapi::bridge::{DEFAULT_PORT, Relay};
+use rsbwapi::transport::PipeTransport;
+use std::net::TcpListener;
+
+fn main() {
+    let address = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| format!("127.0.0.1:{}", DEFAULT_PORT));
+    let listener = TcpListener::bind(&address).expect("Could not listen for bots");
+    println!("Waiting for bots on {}", address);
+    for stream in listener.incoming() {
+        let result = stream
+            .and_then(|stream| {
+                println!("Bot connected from {}", stream.peer_addr()?);
+                Relay::new(PipeTransport::default(), stream)
+            })
+            .map_err(Into::into)
+            .and_then(|mut relay| relay.run());
+        match result {
+            Ok(()) => println!("Bot disconnected"),
+            Err(err) => println!("Relay failed: {}", err),
+        }
+    }
+}
@@ -0,0 +1,403 @@
+//! Runs a bot on a different host than the game.
+//!
+//! A `Relay` sits next to the BWAPI server and mirrors its `BWAPI_GameData` over TCP to a
+//! `TcpTransport`, which a `Client` on the other host uses like any other transport. The bot's
+//! commands are sent back and written into the server's game data by the relay.
+//!
+//! Protocol, all numbers little endian:
+//! * Relay: `RSBWBRG`, protocol version `u8`, `size_of::<BWAPI_GameData>()` as `u32`
+//! * Per frame, relay: length of the game data delta as `u32` followed by the delta to the
+//!   previous frame
+//! * Per frame, client: the entries added to `commands`, `unitCommands`, `shapes` and `strings`,
+//!   each as `start: u32, count: u32` followed by the raw entries
+//!
+//! Neither side trusts the other: frames are validated like recordings before the client uses
+//! them, and the relay checks the bot's entries before writing them into the server's game data.
+use crate::client::ClientError;
+use crate::delta::{self, read_u32};
+use crate::transport::{PipeTransport, SharedGameData, Transport};
+use crate::validate::Entry;
+use bwapi_wrapper::*;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem::offset_of;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::ops::Range;
+
+const MAGIC: &[u8; 7] = b"RSBWBRG";
+const VERSION: u8 = 2;
+
+/// Port the `bwapi_bridge` binary listens on by default
+pub const DEFAULT_PORT: u16 = 9595;
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Writes the entries in `range` of `entries`. Returns the range of `entries` that was sent, in
+/// bytes.
+fn write_entries<T>(
+    out: &mut impl Write,
+    entries: &[T],
+    range: Range<i32>,
+) -> io::Result<Range<usize>> {
+    out.write_all(&(range.start as u32).to_le_bytes())?;
+    out.write_all(&(range.len() as u32).to_le_bytes())?;
+    let range = range.start as usize..range.end as usize;
+    out.write_all(delta::slice_as_bytes(&entries[range.clone()]))?;
+    let size = std::mem::size_of::<T>();
+    Ok(range.start * size..range.end * size)
+}
+
+/// Reads entries written by `write_entries` into `entries` and updates `count`. Entries are only
+/// written once all of them passed validation. Returns the range of `entries` that was written,
+/// in bytes.
+fn read_entries<T: Entry>(
+    input: &mut impl Read,
+    entries: &mut [T],
+    count: &mut i32,
+) -> io::Result<Range<usize>> {
+    let start = read_u32(input)? as usize;
+    let len = read_u32(input)? as usize;
+    let end = start
+        .checked_add(len)
+        .filter(|&end| end <= entries.len())
+        .ok_or_else(|| invalid_data("Too many entries"))?;
+    let size = std::mem::size_of::<T>();
+    let mut bytes = vec![0; len * size];
+    input.read_exact(&mut bytes)?;
+    for entry in bytes.chunks_exact(size) {
+        T::check(entry)?;
+    }
+    for (target, entry) in entries[start..end].iter_mut().zip(bytes.chunks_exact(size)) {
+        // Checked above
+        *target = unsafe { std::ptr::read_unaligned(entry.as_ptr() as *const T) };
+    }
+    *count = end as i32;
+    Ok(start * size..end * size)
+}
+
+/// Copies what the bot sent, the `written` entries and the counts, from `current` to `previous`.
+/// Both sides keep `previous` as the game data the bot has seen, the next frame's delta is
+/// encoded against it.
+fn sync_sent(previous: &mut [u8], current: &[u8], written: [(usize, Range<usize>); 4]) {
+    let counts = [
+        offset_of!(BWAPI_GameData, commandCount),
+        offset_of!(BWAPI_GameData, unitCommandCount),
+        offset_of!(BWAPI_GameData, shapeCount),
+        offset_of!(BWAPI_GameData, stringCount),
+    ]
+    .map(|offset| (offset, 0..std::mem::size_of::<i32>()));
+    for (offset, range) in written.into_iter().chain(counts) {
+        let range = offset + range.start..offset + range.end;
+        previous[range.clone()].copy_from_slice(&current[range]);
+    }
+}
+
+/// Forwards the frames of a BWAPI server to one bot connected via TCP.
+pub struct Relay<T: Transport = PipeTransport> {
+    transport: T,
+    data: Option<SharedGameData>,
+    input: BufReader<TcpStream>,
+    output: BufWriter<TcpStream>,
+    /// The game data as the bot sees it
+    previous: Vec<u8>,
+}
+
+impl<T: Transport> Relay<T> {
+    pub fn new(transport: T, stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            transport,
+            data: None,
+            input: BufReader::new(stream.try_clone()?),
+            output: BufWriter::new(stream),
+            previous: vec![0; std::mem::size_of::<BWAPI_GameData>()],
+        })
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Connects to the server and relays frames until either the server or the bot disconnects.
+    pub fn run(&mut self) -> Result<(), ClientError> {
+        match self.relay() {
+            Err(ClientError::Disconnected) => Ok(()),
+            result => result,
+        }
+    }
+
+    fn relay(&mut self) -> Result<(), ClientError> {
+        let data = self.transport.connect()?;
+        self.data = Some(data.clone());
+        self.output.write_all(MAGIC)?;
+        self.output.write_all(&[VERSION])?;
+        self.output
+            .write_all(&(self.previous.len() as u32).to_le_bytes())?;
+        loop {
+            let delta = delta::encode(&mut self.previous, delta::as_bytes(&*data))?;
+            self.output.write_all(&(delta.len() as u32).to_le_bytes())?;
+            self.output.write_all(&delta)?;
+            self.output.flush()?;
+
+            self.apply_commands()?;
+            self.transport.signal_done()?;
+            self.transport.wait_for_frame()?;
+        }
+    }
+
+    fn apply_commands(&mut self) -> Result<(), ClientError> {
+        let data = self.data.as_ref().expect("Relay is not connected");
+        // Written between frames of the server, like a local client would
+        let data = unsafe { &mut *data.as_ptr() };
+        let written = [
+            (
+                offset_of!(BWAPI_GameData, commands),
+                read_entries(&mut self.input, &mut data.commands, &mut data.commandCount)?,
+            ),
+            (
+                offset_of!(BWAPI_GameData, unitCommands),
+                read_entries(
+                    &mut self.input,
+                    &mut data.unitCommands,
+                    &mut data.unitCommandCount,
+                )?,
+            ),
+            (
+                offset_of!(BWAPI_GameData, shapes),
+                read_entries(&mut self.input, &mut data.shapes, &mut data.shapeCount)?,
+            ),
+            (
+                offset_of!(BWAPI_GameData, strings),
+                read_entries(&mut self.input, &mut data.strings, &mut data.stringCount)?,
+            ),
+        ];
+        // The bot already has these changes, don't send them back with the next frame
+        sync_sent(&mut self.previous, delta::as_bytes(data), written);
+        Ok(())
+    }
+}
+
+/// Receives frames from a `Relay`.
+pub struct TcpTransport {
+    addresses: Vec<SocketAddr>,
+    stream: Option<(BufReader<TcpStream>, BufWriter<TcpStream>)>,
+    data: SharedGameData,
+    /// The game data as last received, untrusted bytes are decoded and validated here first
+    raw: Vec<u8>,
+    /// Entries of `commands`, `unitCommands`, `shapes` and `strings` when the frame was received
+    counts: [i32; 4],
+}
+
+impl TcpTransport {
+    pub fn new(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            addresses: address.to_socket_addrs()?.collect(),
+            stream: None,
+            data: SharedGameData::zeroed(),
+            raw: vec![0; std::mem::size_of::<BWAPI_GameData>()],
+            counts: [0; 4],
+        })
+    }
+
+    fn counts(&self) -> [i32; 4] {
+        [
+            self.data.commandCount,
+            self.data.unitCommandCount,
+            self.data.shapeCount,
+            self.data.stringCount,
+        ]
+    }
+}
+
+unsafe impl Transport for TcpTransport {
+    fn connect(&mut self) -> Result<SharedGameData, ClientError> {
+        let stream = TcpStream::connect(self.addresses.as_slice())?;
+        stream.set_nodelay(true)?;
+        let mut input = BufReader::new(stream.try_clone()?);
+        let mut magic = [0; 7];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("Not a BWAPI relay").into());
+        }
+        let mut version = [0];
+        input.read_exact(&mut version)?;
+        if version[0] != VERSION {
+            return Err(invalid_data("Unsupported relay protocol version").into());
+        }
+        let actual = read_u32(&mut input)? as usize;
+        let expected = std::mem::size_of::<BWAPI_GameData>();
+        if actual != expected {
+            return Err(ClientError::LayoutMismatch { expected, actual });
+        }
+        self.stream = Some((input, BufWriter::new(stream)));
+        self.wait_for_frame()?;
+        Ok(self.data.clone())
+    }
+
+    fn wait_for_frame(&mut self) -> Result<(), ClientError> {
+        let (input, _) = self.stream.as_mut().expect("Transport is not connected");
+        let len = read_u32(input)? as usize;
+        if len > delta::max_encoded_len(self.raw.len()) {
+            return Err(invalid_data("Frame is too large").into());
+        }
+        let mut delta = vec![0; len];
+        input.read_exact(&mut delta)?;
+        // The bot doesn't access the game data while waiting for a frame
+        delta::decode_game_data(&mut self.raw, unsafe { &mut *self.data.as_ptr() }, &delta)?;
+        self.counts = self.counts();
+        Ok(())
+    }
+
+    fn signal_done(&mut self) -> Result<(), ClientError> {
+        let [commands, unit_commands, shapes, strings] = self.counts;
+        let data = &*self.data;
+        let (_, output) = self.stream.as_mut().expect("Transport is not connected");
+        let written = [
+            (
+                offset_of!(BWAPI_GameData, commands),
+                write_entries(output, &data.commands, commands..data.commandCount)?,
+            ),
+            (
+                offset_of!(BWAPI_GameData, unitCommands),
+                write_entries(
+                    output,
+                    &data.unitCommands,
+                    unit_commands..data.unitCommandCount,
+                )?,
+            ),
+            (
+                offset_of!(BWAPI_GameData, shapes),
+                write_entries(output, &data.shapes, shapes..data.shapeCount)?,
+            ),
+            (
+                offset_of!(BWAPI_GameData, strings),
+                write_entries(output, &data.strings, strings..data.stringCount)?,
+            ),
+        ];
+        output.flush()?;
+        sync_sent(&mut self.raw, delta::as_bytes(data), written);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::aimodule::AiModule;
+    use crate::client::Client;
+    use crate::game::Game;
+    use crate::types::c_str_to_str;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Plays `frames` frames and keeps the texts the bot sent, like a BWAPI server.
+    struct StandInServer {
+        data: SharedGameData,
+        frames: i32,
+        texts: Vec<String>,
+    }
+
+    unsafe impl Transport for StandInServer {
+        fn connect(&mut self) -> Result<SharedGameData, ClientError> {
+            Ok(self.data.clone())
+        }
+
+        fn wait_for_frame(&mut self) -> Result<(), ClientError> {
+            let data = unsafe { &mut *self.data.as_ptr() };
+            self.texts.extend(
+                data.strings[..data.stringCount as usize]
+                    .iter()
+                    .map(|string| c_str_to_str(string)),
+            );
+            if data.frameCount == self.frames {
+                return Err(ClientError::Disconnected);
+            }
+            data.commandCount = 0;
+            data.stringCount = 0;
+            data.frameCount += 1;
+            data.eventCount = 1;
+            data.events[0].type_ = BWAPI_EventType_Enum::MatchFrame;
+            Ok(())
+        }
+
+        fn signal_done(&mut self) -> Result<(), ClientError> {
+            Ok(())
+        }
+    }
+
+    struct Chatty;
+
+    impl AiModule for Chatty {
+        fn on_frame(&mut self, game: &Game) {
+            game.send_text(&format!("frame {}", game.get_frame_count()));
+        }
+    }
+
+    #[test]
+    fn should_relay_frames_and_commands_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let relay = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let server = StandInServer {
+                data: SharedGameData::zeroed(),
+                frames: 3,
+                texts: vec![],
+            };
+            let mut relay = Relay::new(server, stream).unwrap();
+            relay.run().unwrap();
+            relay.into_inner().texts
+        });
+
+        let mut client = Client::new(TcpTransport::new(address).unwrap()).unwrap();
+        let mut frames = vec![];
+        while client.update(&mut Chatty).is_ok() {
+            frames.push(client.get_game().get_frame_count());
+        }
+
+        assert_eq!(frames, [1, 2, 3]);
+        assert_eq!(relay.join().unwrap(), ["frame 1", "frame 2", "frame 3"]);
+    }
+
+    #[test]
+    fn should_reject_invalid_entries_from_the_bot() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let bot = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            let mut handshake = [0; 12];
+            stream.read_exact(&mut handshake).unwrap();
+            let len = read_u32(&mut stream).unwrap() as usize;
+            stream.read_exact(&mut vec![0; len]).unwrap();
+
+            let mut shape = vec![0; std::mem::size_of::<BWAPIC_Shape>()];
+            shape[offset_of!(BWAPIC_Shape, isSolid)] = 2;
+            for (start, count) in [(0u32, 0u32), (0, 0), (0, 1)] {
+                stream.write_all(&start.to_le_bytes()).unwrap();
+                stream.write_all(&count.to_le_bytes()).unwrap();
+            }
+            stream.write_all(&shape).unwrap();
+            stream.write_all(&[0; 8]).unwrap();
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        let server = StandInServer {
+            data: SharedGameData::zeroed(),
+            frames: 3,
+            texts: vec![],
+        };
+        let mut relay = Relay::new(server, stream).unwrap();
+        let result = relay.run();
+        bot.join().unwrap();
+
+        assert!(
+            matches!(result, Err(ClientError::Io(err)) if err.kind() == io::ErrorKind::InvalidData)
+        );
+        assert_eq!(relay.into_inner().data.shapeCount, 0);
+    }
+}
@@ -0,0 +1,413 @@
+//! Builds `BWAPI_GameData` from scratch, to test bot logic without StarCraft or a snapshot.
+use crate::player::PlayerId;
+use crate::shm::Shm;
+use crate::transport::SharedGameData;
+use crate::types::Rectangle;
+use crate::unit::UnitId;
+use crate::*;
+use bwapi_wrapper::*;
+
+/// The last player slot is reserved for the neutral player
+const NEUTRAL: usize = 11;
+/// `BWAPI::PlayerTypes::Player`
+const PLAYER_TYPE_PLAYER: i32 = 2;
+/// `BWAPI::PlayerTypes::Neutral`
+const PLAYER_TYPE_NEUTRAL: i32 = 7;
+
+pub struct GameDataBuilder {
+    data: Shm<BWAPI_GameData>,
+    players: usize,
+}
+
+impl GameDataBuilder {
+    /// A map of `width` x `height` tiles, which is completely walkable, buildable, explored and
+    /// visible. Units and players have to be added explicitly, except for the neutral player.
+    pub fn new(width: i32, height: i32) -> Self {
+        let mut data: Shm<BWAPI_GameData> = Shm::zeroed();
+        data.mapWidth = width;
+        data.mapHeight = height;
+        data.isInGame = true;
+        data.self_ = -1;
+        data.enemy = -1;
+        data.neutral = NEUTRAL as i32;
+        data.playerCount = NEUTRAL as i32 + 1;
+        for player in data.players.iter_mut() {
+            player.race = Race::None as i32;
+        }
+        let neutral = &mut data.players[NEUTRAL];
+        neutral.type_ = PLAYER_TYPE_NEUTRAL;
+        neutral.isNeutral = true;
+        for x in 0..width as usize {
+            for y in 0..height as usize {
+                data.isBuildable[x][y] = true;
+                data.isExplored[x][y] = true;
+                data.isVisible[x][y] = true;
+            }
+        }
+        for x in 0..width as usize * 4 {
+            for y in 0..height as usize * 4 {
+                data.isWalkable[x][y] = true;
+            }
+        }
+        Self { data, players: 0 }
+    }
+
+    pub fn walkable(&mut self, area: Rectangle<WalkPosition>, walkable: bool) -> &mut Self {
+        for p in area {
+            self.data.isWalkable[p.x as usize][p.y as usize] = walkable;
+        }
+        self
+    }
+
+    pub fn buildable(&mut self, area: Rectangle<TilePosition>, buildable: bool) -> &mut Self {
+        for p in area {
+            self.data.isBuildable[p.x as usize][p.y as usize] = buildable;
+        }
+        self
+    }
+
+    pub fn explored(&mut self, area: Rectangle<TilePosition>, explored: bool) -> &mut Self {
+        for p in area {
+            self.data.isExplored[p.x as usize][p.y as usize] = explored;
+        }
+        self
+    }
+
+    pub fn visible(&mut self, area: Rectangle<TilePosition>, visible: bool) -> &mut Self {
+        for p in area {
+            self.data.isVisible[p.x as usize][p.y as usize] = visible;
+        }
+        self
+    }
+
+    pub fn creep(&mut self, area: Rectangle<TilePosition>, creep: bool) -> &mut Self {
+        for p in area {
+            self.data.hasCreep[p.x as usize][p.y as usize] = creep;
+        }
+        self
+    }
+
+    pub fn ground_height(&mut self, area: Rectangle<TilePosition>, height: i32) -> &mut Self {
+        for p in area {
+            self.data.getGroundHeight[p.x as usize][p.y as usize] = height;
+        }
+        self
+    }
+
+    pub fn start_location(&mut self, location: TilePosition) -> &mut Self {
+        let i = self.data.startLocationCount as usize;
+        self.data.startLocations[i] = BWAPIC_Position {
+            x: location.x,
+            y: location.y,
+        };
+        self.data.startLocationCount += 1;
+        self
+    }
+
+    /// Adds a player, which is an enemy of all other players. The first player added is `self_`,
+    /// the second one `enemy`.
+    pub fn add_player(&mut self, race: Race) -> PlayerBuilder<'_> {
+        let id = self.players;
+        assert!(id < NEUTRAL, "Too many players");
+        self.players += 1;
+        if self.data.self_ < 0 {
+            self.data.self_ = id as i32;
+        } else if self.data.enemy < 0 {
+            self.data.enemy = id as i32;
+        }
+        for other in 0..id {
+            self.data.players[id].isEnemy[other] = true;
+            self.data.players[other].isEnemy[id] = true;
+        }
+        let player = &mut self.data.players[id];
+        player.race = race as i32;
+        player.type_ = PLAYER_TYPE_PLAYER;
+        player.isParticipating = true;
+        player.force = -1;
+        player.isAlly[id] = true;
+        PlayerBuilder { id, player }
+    }
+
+    /// Makes two players allies instead of enemies.
+    pub fn ally(&mut self, a: PlayerId, b: PlayerId) -> &mut Self {
+        self.data.players[a].isAlly[b] = true;
+        self.data.players[a].isEnemy[b] = false;
+        self.data.players[b].isAlly[a] = true;
+        self.data.players[b].isEnemy[a] = false;
+        self
+    }
+
+    /// Adds a completed unit with full hit points, visible to all players. Use
+    /// `GameDataBuilder::NEUTRAL` for minerals, geysers and other neutral units.
+    pub fn add_unit(
+        &mut self,
+        player: PlayerId,
+        type_: UnitType,
+        position: Position,
+    ) -> UnitBuilder<'_> {
+        let id = self.data.initialUnitCount as UnitId;
+        self.data.initialUnitCount += 1;
+        let unit = &mut self.data.units[id];
+        unit.id = id as i32;
+        unit.replayID = id as i32;
+        unit.player = player as i32;
+        unit.type_ = type_ as i32;
+        unit.positionX = position.x;
+        unit.positionY = position.y;
+        unit.hitPoints = type_.max_hit_points();
+        unit.lastHitPoints = unit.hitPoints;
+        unit.shields = type_.max_shields();
+        unit.energy = type_.max_energy();
+        unit.resources = if type_.is_mineral_field() {
+            1500
+        } else if type_ == UnitType::Resource_Vespene_Geyser {
+            5000
+        } else {
+            0
+        };
+        unit.exists = true;
+        unit.isCompleted = true;
+        unit.isIdle = true;
+        unit.isInterruptible = true;
+        unit.isPowered = true;
+        unit.isDetected = true;
+        unit.isVisible = [true; 9];
+        unit.buildType = UnitType::None as i32;
+        unit.tech = TechType::None as i32;
+        unit.upgrade = UpgradeType::None as i32;
+        unit.order = Order::Nothing as i32;
+        unit.secondaryOrder = Order::Nothing as i32;
+        unit.orderTarget = -1;
+        unit.target = -1;
+        for link in [
+            &mut unit.buildUnit,
+            &mut unit.rallyUnit,
+            &mut unit.addon,
+            &mut unit.nydusExit,
+            &mut unit.powerUp,
+            &mut unit.transport,
+            &mut unit.carrier,
+            &mut unit.hatchery,
+        ] {
+            *link = -1;
+        }
+        UnitBuilder { id, unit }
+    }
+
+    /// Adds a region covering `area`, tiles of later regions overwrite those of earlier ones.
+    pub fn add_region(&mut self, area: Rectangle<TilePosition>) -> u16 {
+        let id = self.data.regionCount as u16;
+        self.data.regionCount += 1;
+        for p in area {
+            self.data.mapTileRegionId[p.x as usize][p.y as usize] = id;
+        }
+        let bounds = Rectangle {
+            tl: area.tl.to_position(),
+            br: (area.br + (1, 1)).to_position() - (1, 1),
+        };
+        let region = &mut self.data.regions[id as usize];
+        region.id = id as i32;
+        region.islandID = 0;
+        region.center_x = (bounds.tl.x + bounds.br.x) / 2;
+        region.center_y = (bounds.tl.y + bounds.br.y) / 2;
+        region.leftMost = bounds.tl.x;
+        region.topMost = bounds.tl.y;
+        region.rightMost = bounds.br.x;
+        region.bottomMost = bounds.br.y;
+        region.isAccessible = true;
+        id
+    }
+
+    pub fn connect_regions(&mut self, a: u16, b: u16) -> &mut Self {
+        for (from, to) in [(a, b), (b, a)] {
+            let region = &mut self.data.regions[from as usize];
+            region.neighbors[region.neighborCount as usize] = to as i32;
+            region.neighborCount += 1;
+        }
+        self
+    }
+
+    /// Direct access to the data, for everything not covered by the builder.
+    pub fn data_mut(&mut self) -> &mut BWAPI_GameData {
+        &mut self.data
+    }
+
+    /// Creates a `Game` as it would be after the match started.
+    pub fn build(mut self) -> Game {
+        let data = &mut *self.data;
+        for unit in data.units[..data.initialUnitCount as usize].iter() {
+            let player = &mut data.players[unit.player as usize];
+            player.allUnitCount[unit.type_ as usize] += 1;
+            player.visibleUnitCount[unit.type_ as usize] += 1;
+            if unit.isCompleted {
+                player.completedUnitCount[unit.type_ as usize] += 1;
+            }
+        }
+        let mut game = Game::new(SharedGameData::new(self.data));
+        game.match_start();
+        game.refresh();
+        game
+    }
+}
+
+impl GameDataBuilder {
+    pub const NEUTRAL: PlayerId = NEUTRAL;
+}
+
+pub struct PlayerBuilder<'a> {
+    id: PlayerId,
+    player: &'a mut BWAPI_PlayerData,
+}
+
+impl PlayerBuilder<'_> {
+    pub fn id(&self) -> PlayerId {
+        self.id
+    }
+
+    pub fn minerals(&mut self, minerals: i32) -> &mut Self {
+        self.player.minerals = minerals;
+        self
+    }
+
+    pub fn gas(&mut self, gas: i32) -> &mut Self {
+        self.player.gas = gas;
+        self
+    }
+
+    /// Supply of the player's race, in BWAPI units (twice the displayed value)
+    pub fn supply(&mut self, used: i32, total: i32) -> &mut Self {
+        let race = self.player.race as usize;
+        self.player.supplyUsed[race] = used;
+        self.player.supplyTotal[race] = total;
+        self
+    }
+
+    pub fn upgrade(&mut self, upgrade: UpgradeType, level: i32) -> &mut Self {
+        self.player.upgradeLevel[upgrade as usize] = level;
+        self
+    }
+
+    pub fn researched(&mut self, tech: TechType) -> &mut Self {
+        self.player.hasResearched[tech as usize] = true;
+        self
+    }
+
+    pub fn start_location(&mut self, location: TilePosition) -> &mut Self {
+        self.player.startLocationX = location.x;
+        self.player.startLocationY = location.y;
+        self
+    }
+}
+
+pub struct UnitBuilder<'a> {
+    id: UnitId,
+    unit: &'a mut BWAPI_UnitData,
+}
+
+impl UnitBuilder<'_> {
+    pub fn id(&self) -> UnitId {
+        self.id
+    }
+
+    pub fn hit_points(&mut self, hit_points: i32) -> &mut Self {
+        self.unit.hitPoints = hit_points;
+        self
+    }
+
+    pub fn shields(&mut self, shields: i32) -> &mut Self {
+        self.unit.shields = shields;
+        self
+    }
+
+    pub fn energy(&mut self, energy: i32) -> &mut Self {
+        self.unit.energy = energy;
+        self
+    }
+
+    pub fn resources(&mut self, resources: i32) -> &mut Self {
+        self.unit.resources = resources;
+        self
+    }
+
+    /// An incomplete unit, with `remaining_build_time` frames to go
+    pub fn constructing(&mut self, remaining_build_time: i32) -> &mut Self {
+        self.unit.isCompleted = false;
+        self.unit.isConstructing = true;
+        self.unit.isIdle = false;
+        self.unit.remainingBuildTime = remaining_build_time;
+        self
+    }
+
+    pub fn order(&mut self, order: Order) -> &mut Self {
+        self.unit.order = order as i32;
+        self.unit.isIdle = false;
+        self
+    }
+
+    pub fn order_target(&mut self, target: UnitId) -> &mut Self {
+        self.unit.orderTarget = target as i32;
+        self
+    }
+
+    pub fn order_target_position(&mut self, position: Position) -> &mut Self {
+        self.unit.orderTargetPositionX = position.x;
+        self.unit.orderTargetPositionY = position.y;
+        self
+    }
+
+    pub fn powered(&mut self, powered: bool) -> &mut Self {
+        self.unit.isPowered = powered;
+        self
+    }
+
+    pub fn lifted(&mut self, lifted: bool) -> &mut Self {
+        self.unit.isLifted = lifted;
+        self
+    }
+
+    pub fn burrowed(&mut self, burrowed: bool) -> &mut Self {
+        self.unit.isBurrowed = burrowed;
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_build_game_with_units_and_players() {
+        let mut builder = GameDataBuilder::new(64, 64);
+        let me = builder.add_player(Race::Protoss).minerals(400).id();
+        let enemy = builder.add_player(Race::Zerg).id();
+        builder.add_unit(me, UnitType::Protoss_Pylon, Position::new(320, 320));
+        builder.add_unit(enemy, UnitType::Zerg_Zergling, Position::new(400, 320));
+        builder.add_unit(
+            GameDataBuilder::NEUTRAL,
+            UnitType::Resource_Mineral_Field,
+            Position::new(800, 800),
+        );
+        builder.buildable(Rectangle::new((20, 20), (30, 30)), false);
+        let game = builder.build();
+
+        assert_eq!(game.self_().unwrap().minerals(), 400);
+        assert!(game.self_().unwrap().is_enemy(&game.enemy().unwrap()));
+        assert_eq!(game.get_static_minerals().len(), 1);
+        assert!(game.has_power_precise(Position::new(330, 330)));
+        assert!(!game.has_power_precise(Position::new(1000, 1000)));
+        assert_eq!(
+            game.get_units_in_radius(Position::new(320, 320), 100, |_: &Unit| true)
+                .len(),
+            2
+        );
+        assert!(
+            game.can_build_here(None::<&Unit>, (4, 4), UnitType::Terran_Barracks, true)
+                .unwrap()
+        );
+        assert!(
+            !game
+                .can_build_here(None::<&Unit>, (19, 19), UnitType::Terran_Barracks, true)
+                .unwrap()
+        );
+    }
+}
@@ -1,22 +1,84 @@
 use crate::game::Game;
-use bwapi_wrapper::*;
 
+use derive_more::{Display, Error};
 use std::ffi::CStr;
-use std::fs::File;
-use std::fs::OpenOptions;
-use std::io::Read;
-use std::io::Write;
-
-use std::thread;
+use std::io;
 use std::time::Duration;
 
 use crate::aimodule::AiModule;
+use crate::event::{Event, Frame};
+use crate::panic_guard::{PanicGuard, PanicPolicy};
+use crate::transport::{PipeTransport, Transport};
+use crate::watchdog::Watchdog;
+
+/// Which BWAPI server to connect to, if multiple games are running.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Instance {
+    /// The first server without a client
+    #[default]
+    Any,
+    /// The server with the given process id
+    ServerPid(u32),
+    /// The server with the given instance number, see `Game::get_instance_number`
+    InstanceNumber(i32),
+}
 
-use super::shm;
+#[derive(Clone, Debug)]
+pub struct ConnectOptions {
+    /// How long to wait for a server, including its first frame. `None` waits forever. On
+    /// Windows, a server that accepts the connection but never sends a frame blocks regardless.
+    pub timeout: Option<Duration>,
+    /// Delay between looking for a server
+    pub retry_interval: Duration,
+    pub instance: Instance,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            retry_interval: Duration::from_millis(1000),
+            instance: Instance::Any,
+        }
+    }
+}
+
+#[derive(Debug, Display, Error)]
+pub enum ClientError {
+    #[display("No BWAPI server was found before the timeout")]
+    Timeout,
+    #[display("Game table was found, but could not map the game data of server {_0}")]
+    GameDataNotMapped(#[error(not(source))] u32),
+    #[display(
+        "Server uses client version {server}, but rsbwapi was built for client version {client}"
+    )]
+    VersionMismatch { server: i32, client: i32 },
+    /// The server's game data has a different size than `BWAPI_GameData`
+    #[display("Server game data has {actual} bytes, but rsbwapi was built for {expected} bytes")]
+    LayoutMismatch { expected: usize, actual: usize },
+    /// The server closed the connection, usually because the game was closed
+    #[display("Connection to the BWAPI server was closed")]
+    Disconnected,
+    #[display("{_0}")]
+    Io(io::Error),
+}
+
+impl From<io::Error> for ClientError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionReset => ClientError::Disconnected,
+            _ => ClientError::Io(err),
+        }
+    }
+}
 
-pub struct Client {
-    pipe: File,
+/// Connects an `AiModule` to a BWAPI server.
+pub struct Client<T: Transport = PipeTransport> {
     game: Game,
+    transport: T,
+    panic_guard: PanicGuard,
 }
 
 pub trait ToStr {
@@ -37,68 +99,384 @@ impl ToStr for [i8] {
     }
 }
 
+/// Receives the events before the first game started.
+struct MenuModule;
+
+impl AiModule for MenuModule {
+    fn on_frame(&mut self, _game: &Game) {}
+}
+
 impl Default for Client {
     fn default() -> Self {
-        'outer: loop {
-            if let Some(game_table) =
-                shm::map_memory::<BWAPI_GameTable>("Local\\bwapi_shared_memory_game_list")
-            {
-                let game_table = game_table.get();
-                for game_instance in game_table.gameInstances.iter() {
-                    if game_instance.serverProcessID != 0 && !game_instance.isConnected {
-                        let pid = game_instance.serverProcessID;
-                        let mut file: File = OpenOptions::new()
-                            .read(true)
-                            .write(true)
-                            .open(format!("\\\\.\\pipe\\bwapi_pipe_{}", pid))
-                            .expect("Game table was found, but could not open bwapi_pipe!");
-                        let mut buf: [u8; 1] = [0];
-                        println!("Connecting to {}", pid);
-                        loop {
-                            file.read_exact(&mut buf)
-                                .expect("Could not read from bwapi_pipe!");
-                            if buf[0] == 2 {
-                                break;
-                            }
-                        }
-                        println!("Connected to {}", pid);
-                        let game_data = &format!("Local\\bwapi_shared_memory_{}", pid);
-                        let game_data: shm::Shm<BWAPI_GameData> = shm::map_memory(game_data)
-                            .expect(
-                                "Game table was found, but could not establish shared memory link.",
-                            );
-                        println!("Client version: {}", game_data.get().client_version);
-                        break 'outer Client {
-                            pipe: file,
-                            game: Game::new(game_data),
-                        };
-                    }
-                }
-            } else {
-                println!("Game table mapping not found.");
-                thread::sleep(Duration::from_millis(1000));
-            }
-        }
+        Self::connect(ConnectOptions::default()).expect("Could not connect to BWAPI server")
     }
 }
+
 impl Client {
-    pub fn update(&mut self, module: &mut impl AiModule) {
-        let mut buf: [u8; 1] = [1];
-        self.pipe
-            .write_all(&buf)
-            .expect("Pipe closed while writing");
+    pub fn connect(options: ConnectOptions) -> Result<Self, ClientError> {
+        Self::new(PipeTransport::new(options))
+    }
+}
+
+impl<T: Transport> Client<T> {
+    pub fn new(mut transport: T) -> Result<Self, ClientError> {
+        let game = Game::new(transport.connect()?);
+        Ok(Self {
+            game,
+            transport,
+            panic_guard: PanicGuard::default(),
+        })
+    }
+
+    /// Finishes the current frame and waits for the next one. Returns `ClientError::Disconnected`
+    /// once the server is gone.
+    pub fn update(&mut self, module: &mut impl AiModule) -> Result<(), ClientError> {
+        let mut frame = self.next_frame()?;
+        while let Some(event) = frame.next() {
+            frame.dispatch(event, module);
+        }
+        Ok(())
+    }
+
+    /// Sets how panics in `AiModule` callbacks are handled by `update` and `run_session`.
+    pub fn set_panic_policy(&mut self, policy: PanicPolicy) {
+        self.panic_guard = PanicGuard::new(policy);
+    }
+
+    /// Tracks the frame times of the bot against tournament limits, see `Watchdog::sscait`.
+    pub fn set_watchdog(&mut self, watchdog: Option<Watchdog>) {
+        self.game.set_watchdog(watchdog);
+    }
+
+    pub fn panic_guard(&self) -> &PanicGuard {
+        &self.panic_guard
+    }
+
+    /// Finishes the current frame and waits for the next one, whose events can then be drained
+    /// from the returned `Frame` instead of being passed to an `AiModule`.
+    pub fn next_frame(&mut self) -> Result<Frame<'_>, ClientError> {
+        self.transport.signal_done()?;
+        self.transport.wait_for_frame()?;
+        Ok(Frame::new(&mut self.game, &mut self.panic_guard))
+    }
+
+    /// Plays games until the server disconnects. `build_module` is called at the start of every
+    /// game, after the game state was reset for it, and receives the module of the previous game,
+    /// if there was one.
+    pub fn run_session<M: AiModule>(
+        &mut self,
+        mut build_module: impl FnMut(&Game, Option<M>) -> M,
+    ) -> Result<(), ClientError> {
+        let mut module = None;
         loop {
-            self.pipe
-                .read_exact(&mut buf)
-                .expect("Pipe closed while reading");
-            if buf[0] == 2 {
-                break;
+            let mut frame = match self.next_frame() {
+                Err(ClientError::Disconnected) => return Ok(()),
+                frame => frame?,
+            };
+            while let Some(event) = frame.next() {
+                // Only now the game state of the previous game is gone
+                if event == Event::MatchStart {
+                    module = Some(build_module(frame.game(), module.take()));
+                }
+                match module.as_mut() {
+                    Some(module) => frame.dispatch(event, module),
+                    None => frame.dispatch(event, &mut MenuModule),
+                }
             }
         }
-        self.game.handle_events(module);
     }
 
     pub fn get_game(&self) -> &Game {
         &self.game
     }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transport::{MemoryTransport, SharedGameData};
+    #[cfg(unix)]
+    use crate::{
+        shm,
+        transport::{game_data_name, pipe_name},
+    };
+    use bwapi_wrapper::*;
+    #[cfg(unix)]
+    use std::io::{Read, Write};
+    #[cfg(unix)]
+    use std::os::unix::net::UnixListener;
+    #[cfg(unix)]
+    use std::thread;
+
+    struct FrameCounter(Vec<i32>);
+
+    impl AiModule for FrameCounter {
+        fn on_frame(&mut self, game: &Game) {
+            self.0.push(game.get_frame_count());
+        }
+    }
+
+    /// Stand-in servers get their own "process id", so that the socket and game data names differ
+    /// between tests and test processes. Linux pids fit into 22 bits.
+    #[cfg(unix)]
+    fn server_id() -> u32 {
+        static NEXT: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+        (std::process::id() << 8) | (NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed) & 0xff)
+    }
+
+    /// Plays the server side of the handshake for `frames` frames, like OpenBW would.
+    #[cfg(unix)]
+    fn stand_in_server(
+        game_table_name: String,
+        client_version: i32,
+        frames: i32,
+    ) -> thread::JoinHandle<()> {
+        stand_in_server_at(game_table_name, 0, client_version, frames)
+    }
+
+    /// Entries before `instance` in the game table belong to servers that crashed.
+    #[cfg(unix)]
+    fn stand_in_server_at(
+        game_table_name: String,
+        instance: usize,
+        client_version: i32,
+        frames: i32,
+    ) -> thread::JoinHandle<()> {
+        let pid = server_id();
+        let crashed: Vec<_> = (0..instance).map(|_| server_id()).collect();
+        let _ = std::fs::remove_file(pipe_name(pid));
+        let listener = UnixListener::bind(pipe_name(pid)).unwrap();
+        thread::spawn(move || {
+            let mut data: shm::Shm<BWAPI_GameData> =
+                shm::create_memory(&game_data_name(pid)).unwrap();
+            let mut table: shm::Shm<BWAPI_GameTable> =
+                shm::create_memory(&game_table_name).unwrap();
+            for (game_instance, &crashed) in table.gameInstances.iter_mut().zip(&crashed) {
+                game_instance.serverProcessID = crashed;
+            }
+            table.gameInstances[instance].serverProcessID = pid;
+            data.client_version = client_version;
+
+            let (mut pipe, _) = listener.accept().unwrap();
+            pipe.write_all(&[2]).unwrap();
+            let mut buf = [0u8];
+            for _ in 0..frames {
+                pipe.read_exact(&mut buf).unwrap();
+                assert_eq!(buf[0], 1);
+                data.frameCount += 1;
+                data.eventCount = 1;
+                data.events[0].type_ = BWAPI_EventType_Enum::MatchFrame;
+                pipe.write_all(&[2]).unwrap();
+            }
+            if frames == 0 {
+                // Keep the game data around until the client is done with the handshake
+                let _ = pipe.read(&mut buf);
+            }
+
+            shm::unlink_memory(&game_table_name);
+            shm::unlink_memory(&game_data_name(pid));
+            std::fs::remove_file(pipe_name(pid)).unwrap();
+        })
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_step_frames_with_stand_in_server() {
+        let game_table_name = format!("/rsbwapi_test_game_list_{}", std::process::id());
+        let server = stand_in_server(game_table_name.clone(), CLIENT_VERSION, 3);
+
+        let mut client = Client::new(PipeTransport::with_game_table(
+            ConnectOptions::default(),
+            &game_table_name,
+        ))
+        .unwrap();
+        let mut module = FrameCounter(vec![]);
+        for _ in 0..3 {
+            client.update(&mut module).unwrap();
+        }
+
+        server.join().unwrap();
+        assert_eq!(module.0, [1, 2, 3]);
+        assert!(matches!(
+            client.update(&mut module),
+            Err(ClientError::Disconnected)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_reject_server_with_other_client_version() {
+        let game_table_name = format!("/rsbwapi_test_version_game_list_{}", std::process::id());
+        let server = stand_in_server(game_table_name.clone(), CLIENT_VERSION + 1, 0);
+
+        let result = Client::new(PipeTransport::with_game_table(
+            ConnectOptions::default(),
+            &game_table_name,
+        ));
+        let Err(err) = result else {
+            panic!("Client connected to an incompatible server");
+        };
+
+        assert!(matches!(
+            err,
+            ClientError::VersionMismatch { server, client: CLIENT_VERSION } if server == CLIENT_VERSION + 1
+        ));
+        server.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_skip_game_table_entries_of_crashed_servers() {
+        let game_table_name = format!("/rsbwapi_test_crashed_game_list_{}", std::process::id());
+        let server = stand_in_server_at(game_table_name.clone(), 2, CLIENT_VERSION, 1);
+
+        let mut client = Client::new(PipeTransport::with_game_table(
+            ConnectOptions::default(),
+            &game_table_name,
+        ))
+        .unwrap();
+        let mut module = FrameCounter(vec![]);
+        client.update(&mut module).unwrap();
+
+        server.join().unwrap();
+        assert_eq!(module.0, [1]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_time_out_if_server_sends_no_frame() {
+        let pid = server_id();
+        let game_table_name = format!("/rsbwapi_test_silent_game_list_{}", std::process::id());
+        let _ = std::fs::remove_file(pipe_name(pid));
+        let _listener = UnixListener::bind(pipe_name(pid)).unwrap();
+        let mut table: shm::Shm<BWAPI_GameTable> = shm::create_memory(&game_table_name).unwrap();
+        table.gameInstances[0].serverProcessID = pid;
+        let options = ConnectOptions {
+            timeout: Some(Duration::from_millis(100)),
+            retry_interval: Duration::from_millis(10),
+            ..Default::default()
+        };
+
+        let result = Client::new(PipeTransport::with_game_table(options, &game_table_name));
+        shm::unlink_memory(&game_table_name);
+        std::fs::remove_file(pipe_name(pid)).unwrap();
+
+        assert!(matches!(result, Err(ClientError::Timeout)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_time_out_without_server() {
+        let options = ConnectOptions {
+            timeout: Some(Duration::from_millis(50)),
+            retry_interval: Duration::from_millis(10),
+            ..Default::default()
+        };
+        let result = Client::new(PipeTransport::with_game_table(
+            options,
+            "/rsbwapi_test_missing_game_list",
+        ));
+        assert!(matches!(result, Err(ClientError::Timeout)));
+    }
+
+    /// Replays a fixed list of frames, then disconnects. Only the first game has a mineral field.
+    struct ScriptedTransport {
+        data: SharedGameData,
+        frames: Vec<(bool, BWAPI_EventType_Enum)>,
+        games: u32,
+    }
+
+    unsafe impl Transport for ScriptedTransport {
+        fn connect(&mut self) -> Result<SharedGameData, ClientError> {
+            Ok(self.data.clone())
+        }
+
+        fn wait_for_frame(&mut self) -> Result<(), ClientError> {
+            if self.frames.is_empty() {
+                return Err(ClientError::Disconnected);
+            }
+            let (in_game, event) = self.frames.remove(0);
+            let data = unsafe { &mut *self.data.as_ptr() };
+            data.frameCount += 1;
+            data.isInGame = in_game;
+            data.eventCount = 1;
+            data.events[0].type_ = event;
+            if event == BWAPI_EventType_Enum::MatchStart {
+                self.games += 1;
+                data.playerCount = 1;
+                data.initialUnitCount = 1;
+                data.units[0].exists = self.games == 1;
+                data.units[0].type_ =
+                    bwapi_wrapper::prelude::UnitType::Resource_Mineral_Field as i32;
+            }
+            Ok(())
+        }
+
+        fn signal_done(&mut self) -> Result<(), ClientError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_build_module_for_every_game_in_session() {
+        use BWAPI_EventType_Enum::*;
+        let mut client = Client::new(ScriptedTransport {
+            data: SharedGameData::zeroed(),
+            frames: vec![
+                (false, MenuFrame),
+                (true, MatchStart),
+                (true, MatchFrame),
+                (true, MatchEnd),
+                (false, MenuFrame),
+                (true, MatchStart),
+                (true, MatchFrame),
+            ],
+            games: 0,
+        })
+        .unwrap();
+        let mut minerals = vec![];
+
+        client
+            .run_session(|game, previous: Option<FrameCounter>| {
+                minerals.push(game.get_static_minerals().len());
+                previous.unwrap_or(FrameCounter(vec![]))
+            })
+            .unwrap();
+
+        assert_eq!(minerals, [1, 0]);
+    }
+
+    #[test]
+    fn should_handle_events_from_memory() {
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        let mut module = FrameCounter(vec![]);
+        for frame in 1..=2 {
+            let data = client.transport_mut().data_mut();
+            data.frameCount = frame;
+            data.eventCount = 1;
+            data.events[0].type_ = BWAPI_EventType_Enum::MatchFrame;
+            client.update(&mut module).unwrap();
+        }
+
+        assert_eq!(module.0, [1, 2]);
+    }
+
+    #[test]
+    fn should_keep_game_data_alive_after_client_is_dropped() {
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        client.transport_mut().data_mut().frameCount = 7;
+        let game = client.get_game().clone();
+
+        drop(client);
+
+        assert_eq!(game.get_frame_count(), 7);
+    }
 }
@@ -1,5 +1,5 @@
 use crate::types::{Color, TextSize};
-use crate::{Game, Player};
+use crate::{Game, Player, Unit};
 use bwapi_wrapper::prelude::*;
 use bwapi_wrapper::*;
 use core::cell::RefMut;
@@ -144,7 +144,9 @@ pub(crate) struct Commands {
     shapes: Vec<BWAPIC_Shape>,
 }
 
-pub(crate) enum Command {
+/// A command which carries a string and is only encoded when committed.
+#[derive(Clone, Debug)]
+pub enum Command {
     DrawText {
         ctype: CoordinateType,
         x: i32,
@@ -168,11 +170,114 @@ impl Commands {
     }
 }
 
+/// Everything queued in the current frame, which will be sent to BWAPI at its end. Mainly useful
+/// to assert what a bot did in tests.
+#[derive(Clone)]
+pub struct QueuedCommands {
+    pub unit_commands: Vec<UnitCommand>,
+    pub game_commands: Vec<BWAPIC_Command>,
+    pub commands: Vec<Command>,
+    pub shapes: Vec<BWAPIC_Shape>,
+}
+
+impl QueuedCommands {
+    pub fn commands_of<'a>(&'a self, unit: &Unit) -> impl Iterator<Item = &'a UnitCommand> {
+        let id = unit.get_id() as i32;
+        self.unit_commands
+            .iter()
+            .filter(move |cmd| cmd.unitIndex == id)
+    }
+
+    pub fn was_ordered(&self, unit: &Unit, type_: UnitCommandType) -> bool {
+        self.commands_of(unit).any(|cmd| cmd.get_type() == type_)
+    }
+
+    pub fn was_ordered_to_attack(&self, unit: &Unit, target: &Unit) -> bool {
+        self.commands_of(unit).any(|cmd| {
+            cmd.get_type() == UnitCommandType::Attack_Unit
+                && cmd.targetIndex == target.get_id() as i32
+        })
+    }
+
+    pub fn was_ordered_to_attack_move(&self, unit: &Unit, position: Position) -> bool {
+        self.commands_of(unit).any(|cmd| {
+            cmd.get_type() == UnitCommandType::Attack_Move && cmd.get_target_position() == position
+        })
+    }
+
+    pub fn was_ordered_to_move(&self, unit: &Unit, position: Position) -> bool {
+        self.commands_of(unit).any(|cmd| {
+            cmd.get_type() == UnitCommandType::Move && cmd.get_target_position() == position
+        })
+    }
+
+    pub fn was_ordered_to_gather(&self, unit: &Unit, target: &Unit) -> bool {
+        self.commands_of(unit).any(|cmd| {
+            cmd.get_type() == UnitCommandType::Gather && cmd.targetIndex == target.get_id() as i32
+        })
+    }
+
+    pub fn was_ordered_to_build(
+        &self,
+        unit: &Unit,
+        type_: UnitType,
+        position: TilePosition,
+    ) -> bool {
+        self.commands_of(unit).any(|cmd| {
+            cmd.get_type() == UnitCommandType::Build
+                && cmd.get_unit_type() == type_
+                && cmd.get_target_tile_position() == position
+        })
+    }
+
+    pub fn was_ordered_to_train(&self, unit: &Unit, type_: UnitType) -> bool {
+        self.commands_of(unit).any(|cmd| {
+            matches!(
+                cmd.get_type(),
+                UnitCommandType::Train | UnitCommandType::Morph
+            ) && cmd.get_unit_type() == type_
+        })
+    }
+
+    /// Messages sent with `Game::send_text`
+    pub fn sent_texts(&self) -> Vec<&str> {
+        self.commands
+            .iter()
+            .filter_map(|cmd| match cmd {
+                Command::SendText { message, .. } => Some(message.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Texts drawn with any of the `draw_text*` methods
+    pub fn drawn_texts(&self) -> Vec<&str> {
+        self.commands
+            .iter()
+            .filter_map(|cmd| match cmd {
+                Command::DrawText { string, .. } => Some(string.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
 impl Game {
     fn cmd(&'_ self) -> RefMut<'_, Commands> {
         self.inner.cmd.borrow_mut()
     }
 
+    /// The commands issued so far in this frame.
+    pub fn get_queued_commands(&self) -> QueuedCommands {
+        let cmd = self.inner.cmd.borrow();
+        QueuedCommands {
+            unit_commands: cmd.unit_commands.clone(),
+            game_commands: cmd.game_commands.clone(),
+            commands: cmd.commands.clone(),
+            shapes: cmd.shapes.clone(),
+        }
+    }
+
     pub fn send_text_ex(&self, to_allies: bool, message: &str) {
         self.cmd().commands.push(Command::SendText {
             to_allies,
@@ -623,3 +728,34 @@ impl Game {
         });
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::builder::GameDataBuilder;
+    use crate::*;
+
+    #[test]
+    fn should_report_queued_orders() {
+        let mut builder = GameDataBuilder::new(64, 64);
+        let me = builder.add_player(Race::Terran).id();
+        let enemy = builder.add_player(Race::Zerg).id();
+        let marine = builder
+            .add_unit(me, UnitType::Terran_Marine, Position::new(320, 320))
+            .id();
+        let zergling = builder
+            .add_unit(enemy, UnitType::Zerg_Zergling, Position::new(400, 320))
+            .id();
+        let game = builder.build();
+        let marine = game.get_unit(marine).unwrap();
+        let zergling = game.get_unit(zergling).unwrap();
+
+        assert!(marine.attack(&zergling).unwrap());
+        game.send_text("gl hf");
+
+        let queued = game.get_queued_commands();
+        assert!(queued.was_ordered_to_attack(&marine, &zergling));
+        assert!(!queued.was_ordered_to_attack(&zergling, &marine));
+        assert!(!queued.was_ordered_to_move(&marine, Position::new(400, 320)));
+        assert_eq!(queued.sent_texts(), ["gl hf"]);
+    }
+}
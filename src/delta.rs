@@ -0,0 +1,184 @@
+//! Encodes the changes between two versions of a buffer, used to store or send `BWAPI_GameData`
+//! frames without copying the whole (several MB large) struct each time.
+//!
+//! A delta is a zlib stream of runs: `offset: u32, len: u32, bytes: [u8; len]`, all little
+//! endian. Runs are ordered by offset and bytes outside of runs are unchanged.
+
+use crate::validate;
+use bwapi_wrapper::BWAPI_GameData;
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+/// Equal bytes between two changed regions which are still merged into a single run
+const MERGE_GAP: usize = 16;
+
+/// Encodes the difference of `current` to `previous` and updates `previous` to `current`.
+pub(crate) fn encode(previous: &mut [u8], current: &[u8]) -> io::Result<Vec<u8>> {
+    assert_eq!(previous.len(), current.len());
+    let mut encoder = ZlibEncoder::new(vec![], Compression::fast());
+    let mut i = 0;
+    while i < current.len() {
+        if previous[i] == current[i] {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let mut end = i + 1;
+        let mut j = end;
+        while j < current.len() && j - end <= MERGE_GAP {
+            if previous[j] != current[j] {
+                end = j + 1;
+            }
+            j += 1;
+        }
+        encoder.write_all(&(start as u32).to_le_bytes())?;
+        encoder.write_all(&((end - start) as u32).to_le_bytes())?;
+        encoder.write_all(&current[start..end])?;
+        previous[start..end].copy_from_slice(&current[start..end]);
+        i = end;
+    }
+    encoder.finish()
+}
+
+/// Upper bound of the length of an encoded delta of a buffer with `len` bytes: A single run over
+/// all of it, plus the overhead of zlib for incompressible data.
+pub(crate) fn max_encoded_len(len: usize) -> usize {
+    len + len / 1000 + 64
+}
+
+/// Upper bound of the inflated length of a delta of a buffer with `len` bytes: Runs are at least
+/// `MERGE_GAP` bytes apart, each adds an 8 byte header.
+fn max_decoded_len(len: usize) -> u64 {
+    (len + 8 * (len / MERGE_GAP + 1)) as u64
+}
+
+/// Applies a delta created by `encode` to `target`. Returns the ranges that were written.
+pub(crate) fn decode(target: &mut [u8], delta: &[u8]) -> io::Result<Vec<Range<usize>>> {
+    let limit = max_decoded_len(target.len());
+    let mut decoder = ZlibDecoder::new(delta).take(limit);
+    let mut header = [0; 8];
+    let mut runs = vec![];
+    loop {
+        match decoder.read_exact(&mut header) {
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                if decoder.limit() == 0 && decoder.get_mut().read(&mut [0])? > 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Delta is longer than any valid one",
+                    ));
+                }
+                return Ok(runs);
+            }
+            result => result?,
+        }
+        let start = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let len = u32::from_le_bytes(header[4..].try_into().unwrap()) as usize;
+        let run = start
+            .checked_add(len)
+            .and_then(|end| target.get_mut(start..end))
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "Delta run is out of bounds")
+            })?;
+        decoder.read_exact(run)?;
+        runs.push(start..start + len);
+    }
+}
+
+/// Applies a delta of a `BWAPI_GameData` to `raw`, the bytes of the game data as last received.
+/// `target` holds the same bytes and is only updated once the new frame passed validation, on
+/// error both keep the previous frame.
+pub(crate) fn decode_game_data(
+    raw: &mut [u8],
+    target: &mut BWAPI_GameData,
+    delta: &[u8],
+) -> io::Result<()> {
+    let result = decode(raw, delta).and_then(|runs| validate::game_data(raw).map(|_| runs));
+    let target = target as *mut BWAPI_GameData as *mut u8;
+    match result {
+        Ok(runs) => {
+            for run in runs {
+                // Copying the changed bytes makes `target` equal to `raw`, which is valid
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        raw[run.clone()].as_ptr(),
+                        target.add(run.start),
+                        run.len(),
+                    );
+                }
+            }
+            Ok(())
+        }
+        Err(err) => {
+            let previous = unsafe { std::slice::from_raw_parts(target, raw.len()) };
+            raw.copy_from_slice(previous);
+            Err(err)
+        }
+    }
+}
+
+pub(crate) fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    input.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Views a plain data struct like `BWAPI_GameData` as bytes.
+pub(crate) fn as_bytes<T>(data: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(data as *const T as *const u8, std::mem::size_of::<T>()) }
+}
+
+/// Views a slice of plain data structs as bytes.
+#[cfg(feature = "bridge")]
+pub(crate) fn slice_as_bytes<T>(data: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data)) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_restore_changes() {
+        let mut previous = vec![0; 1000];
+        let mut current = vec![0; 1000];
+        current[3] = 1;
+        current[10] = 2;
+        current[500..600].fill(7);
+        current[999] = 3;
+
+        let delta = encode(&mut previous, &current).unwrap();
+        assert_eq!(previous, current);
+
+        let mut restored = vec![0; 1000];
+        decode(&mut restored, &delta).unwrap();
+        assert_eq!(restored, current);
+    }
+
+    #[test]
+    fn should_reject_runs_out_of_bounds() {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::fast());
+        encoder.write_all(&u32::MAX.to_le_bytes()).unwrap();
+        encoder.write_all(&u32::MAX.to_le_bytes()).unwrap();
+        let delta = encoder.finish().unwrap();
+
+        let result = decode(&mut [0; 1000], &delta);
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn should_reject_deltas_longer_than_any_valid_one() {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::fast());
+        for _ in 0..1000 {
+            encoder.write_all(&[0; 8]).unwrap();
+        }
+        let delta = encoder.finish().unwrap();
+
+        let result = decode(&mut [0; 100], &delta);
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,263 @@
+use crate::aimodule::AiModule;
+use crate::game::{FrameStart, Game};
+use crate::panic_guard::PanicGuard;
+use crate::player::Player;
+use crate::unit::Unit;
+use bwapi_wrapper::prelude::Position;
+use bwapi_wrapper::*;
+#[cfg(feature = "metrics")]
+use metered::measure;
+
+/// An event of the current frame, see `Client::next_frame`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    MatchStart,
+    MatchEnd { winner: bool },
+    MatchFrame,
+    MenuFrame,
+    SendText(String),
+    ReceiveText { player: Player, text: String },
+    PlayerLeft(Player),
+    NukeDetect(Position),
+    UnitDiscover(Unit),
+    UnitEvade(Unit),
+    UnitShow(Unit),
+    UnitHide(Unit),
+    UnitCreate(Unit),
+    UnitDestroy(Unit),
+    UnitMorph(Unit),
+    UnitRenegade(Unit),
+    SaveGame(String),
+    UnitComplete(Unit),
+}
+
+impl Event {
+    /// Calls the `AiModule` callback for this event.
+    pub fn dispatch(self, game: &Game, module: &mut impl AiModule) {
+        match self {
+            Event::MatchStart => measure!(&game.get_metrics().on_start, module.on_start(game)),
+            Event::MatchEnd { winner } => {
+                measure!(&game.get_metrics().on_end, module.on_end(game, winner))
+            }
+            Event::MatchFrame => measure!(&game.get_metrics().on_frame, module.on_frame(game)),
+            Event::MenuFrame => {}
+            Event::SendText(text) => measure!(
+                &game.get_metrics().on_send_text,
+                module.on_send_text(game, text)
+            ),
+            Event::ReceiveText { player, text } => measure!(
+                &game.get_metrics().on_receive_text,
+                module.on_receive_text(game, player, text)
+            ),
+            Event::PlayerLeft(player) => measure!(
+                &game.get_metrics().on_player_left,
+                module.on_player_left(game, player)
+            ),
+            Event::NukeDetect(position) => measure!(
+                &game.get_metrics().on_nuke_detect,
+                module.on_nuke_detect(game, position)
+            ),
+            Event::UnitDiscover(unit) => measure!(
+                &game.get_metrics().on_unit_discover,
+                module.on_unit_discover(game, unit)
+            ),
+            Event::UnitEvade(unit) => measure!(
+                &game.get_metrics().on_unit_evade,
+                module.on_unit_evade(game, unit)
+            ),
+            Event::UnitShow(unit) => measure!(
+                &game.get_metrics().on_unit_show,
+                module.on_unit_show(game, unit)
+            ),
+            Event::UnitHide(unit) => measure!(
+                &game.get_metrics().on_unit_hide,
+                module.on_unit_hide(game, unit)
+            ),
+            Event::UnitCreate(unit) => measure!(
+                &game.get_metrics().on_unit_create,
+                module.on_unit_create(game, unit)
+            ),
+            Event::UnitDestroy(unit) => measure!(
+                &game.get_metrics().on_unit_destroy,
+                module.on_unit_destroy(game, unit)
+            ),
+            Event::UnitMorph(unit) => measure!(
+                &game.get_metrics().on_unit_morph,
+                module.on_unit_morph(game, unit)
+            ),
+            Event::UnitRenegade(unit) => measure!(
+                &game.get_metrics().on_unit_renegade,
+                module.on_unit_renegade(game, unit)
+            ),
+            Event::SaveGame(name) => measure!(
+                &game.get_metrics().on_save_game,
+                module.on_save_game(game, name)
+            ),
+            Event::UnitComplete(unit) => measure!(
+                &game.get_metrics().on_unit_complete,
+                module.on_unit_complete(game, unit)
+            ),
+        }
+    }
+}
+
+/// The events of one frame, returned by `Client::next_frame`.
+///
+/// The game state is updated event by event while iterating, just like before each `AiModule`
+/// callback. Dropping the frame processes the remaining events and commits all commands issued
+/// in this frame. `Client::update` goes through a `Frame` as well, so the watchdog, the panic
+/// policy and the metrics work the same way for both.
+pub struct Frame<'a> {
+    game: &'a mut Game,
+    guard: &'a mut PanicGuard,
+    start: Option<FrameStart>,
+    next: usize,
+    /// The event returned last, which still needs to be finished
+    current: Option<BWAPIC_Event>,
+}
+
+impl<'a> Frame<'a> {
+    pub(crate) fn new(game: &'a mut Game, guard: &'a mut PanicGuard) -> Self {
+        let start = game.begin_frame(guard);
+        Self {
+            game,
+            guard,
+            start: Some(start),
+            next: 0,
+            current: None,
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        self.game
+    }
+
+    /// Passes `event` to the matching callback of `module`, with panics handled according to the
+    /// `PanicPolicy` of the client.
+    pub fn dispatch(&mut self, event: Event, module: &mut impl AiModule) {
+        let game = &*self.game;
+        self.guard.call(game, || event.dispatch(game, module));
+    }
+
+    fn finish_current(&mut self) {
+        if let Some(event) = self.current.take() {
+            self.game.end_event(event);
+        }
+    }
+}
+
+impl Iterator for Frame<'_> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        self.finish_current();
+        while let Some(&event) = self.game.raw_events().get(self.next) {
+            self.next += 1;
+            self.current = Some(event);
+            if let Some(event) = self.game.begin_event(event) {
+                return Some(event);
+            }
+            self.finish_current();
+        }
+        None
+    }
+}
+
+impl Drop for Frame<'_> {
+    fn drop(&mut self) {
+        // Don't touch the game again while a callback panic unwinds
+        if std::thread::panicking() {
+            return;
+        }
+        self.by_ref().for_each(drop);
+        if let Some(start) = self.start.take() {
+            self.game.end_frame(start);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::Client;
+    use crate::transport::MemoryTransport;
+
+    fn set_events(client: &mut Client<MemoryTransport>, events: &[(BWAPI_EventType_Enum, i32)]) {
+        let data = client.transport_mut().data_mut();
+        data.frameCount += 1;
+        data.eventCount = events.len() as i32;
+        for (target, &(type_, v1)) in data.events.iter_mut().zip(events) {
+            *target = BWAPIC_Event { type_, v1, v2: 0 };
+        }
+    }
+
+    #[test]
+    fn should_iterate_events_of_frame() {
+        use BWAPI_EventType_Enum::*;
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        client.transport_mut().data_mut().units[3].exists = true;
+        set_events(
+            &mut client,
+            &[
+                (MatchFrame, 0),
+                (None, 0),
+                (NukeDetect, 5),
+                (UnitRenegade, 3),
+            ],
+        );
+
+        let mut frame = client.next_frame().unwrap();
+        assert_eq!(frame.next(), Some(Event::MatchFrame));
+        assert_eq!(
+            frame.next(),
+            Some(Event::NukeDetect(Position { x: 5, y: 0 }))
+        );
+        let Some(Event::UnitRenegade(unit)) = frame.next() else {
+            panic!("Expected renegade event");
+        };
+        assert_eq!(unit.get_id(), 3);
+        assert_eq!(frame.next(), Option::None);
+    }
+
+    #[derive(Default)]
+    struct Renegades(Vec<usize>, usize);
+
+    impl AiModule for Renegades {
+        fn on_frame(&mut self, _game: &Game) {}
+
+        fn on_unit_renegade(&mut self, _game: &Game, unit: Unit) {
+            self.0.push(unit.get_id());
+        }
+
+        fn on_unit_morph(&mut self, _game: &Game, _unit: Unit) {
+            self.1 += 1;
+        }
+    }
+
+    #[test]
+    fn should_call_on_unit_renegade_for_renegade_event() {
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        client.transport_mut().data_mut().units[3].exists = true;
+        set_events(&mut client, &[(BWAPI_EventType_Enum::UnitRenegade, 3)]);
+        let mut module = Renegades::default();
+
+        client.update(&mut module).unwrap();
+
+        assert_eq!(module.0, [3]);
+        assert_eq!(module.1, 0);
+    }
+
+    #[test]
+    fn should_time_frames_of_next_frame_with_watchdog() {
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        client.set_watchdog(Some(crate::watchdog::Watchdog::sscait()));
+        client.transport_mut().data_mut().isInGame = true;
+        set_events(&mut client, &[(BWAPI_EventType_Enum::MatchFrame, 0)]);
+
+        let frame = client.next_frame().unwrap();
+        assert_eq!(frame.game().get_watchdog().unwrap().get_frames(), 0);
+        drop(frame);
+
+        assert_eq!(client.get_game().get_watchdog().unwrap().get_frames(), 1);
+    }
+}
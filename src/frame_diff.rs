@@ -0,0 +1,182 @@
+use crate::player::PlayerId;
+use crate::unit::{Unit, UnitId};
+use bwapi_wrapper::prelude::*;
+use bwapi_wrapper::*;
+use std::collections::HashMap;
+
+/// Fields of a unit compared between frames, see `Game::get_previous_unit_state`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnitState {
+    pub type_: UnitType,
+    pub player: PlayerId,
+    pub position: Position,
+    pub hit_points: i32,
+    pub shields: i32,
+    pub order: Order,
+    pub training_queue_count: i32,
+}
+
+impl UnitState {
+    fn new(data: &BWAPI_UnitData) -> Self {
+        Self {
+            type_: UnitType::new(data.type_),
+            player: data.player as PlayerId,
+            position: Position {
+                x: data.positionX,
+                y: data.positionY,
+            },
+            hit_points: data.hitPoints,
+            shields: data.shields,
+            order: Order::new(data.order),
+            training_queue_count: data.trainingQueueCount,
+        }
+    }
+}
+
+/// Fields of a player compared between frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PlayerState {
+    pub minerals: i32,
+    pub gas: i32,
+    pub gathered_minerals: i32,
+    pub gathered_gas: i32,
+}
+
+impl PlayerState {
+    fn new(data: &BWAPI_PlayerData) -> Self {
+        Self {
+            minerals: data.minerals,
+            gas: data.gas,
+            gathered_minerals: data.gatheredMinerals,
+            gathered_gas: data.gatheredGas,
+        }
+    }
+}
+
+/// The state of the previous and the current frame.
+#[derive(Default)]
+pub(crate) struct FrameDiff {
+    frame: i32,
+    previous: HashMap<UnitId, UnitState>,
+    current: HashMap<UnitId, UnitState>,
+    previous_players: Vec<PlayerState>,
+    current_players: Vec<PlayerState>,
+    /// Units of the current frame with less hit points or shields than in the previous one
+    damaged: Vec<UnitId>,
+}
+
+impl FrameDiff {
+    pub(crate) fn clear(&mut self) {
+        self.previous.clear();
+        self.current.clear();
+        self.previous_players.clear();
+        self.current_players.clear();
+        self.damaged.clear();
+    }
+
+    /// Records the units of the current frame. The state of the last frame becomes the previous
+    /// state once the frame count changes.
+    pub(crate) fn update(&mut self, data: &BWAPI_GameData, units: &[Unit]) {
+        if data.frameCount != self.frame {
+            self.frame = data.frameCount;
+            std::mem::swap(&mut self.previous, &mut self.current);
+            std::mem::swap(&mut self.previous_players, &mut self.current_players);
+            self.current.clear();
+        }
+        self.current_players.clear();
+        self.current_players.extend(
+            data.players[..data.playerCount as usize]
+                .iter()
+                .map(PlayerState::new),
+        );
+        self.damaged.clear();
+        for unit in units {
+            let id = unit.get_id();
+            let state = UnitState::new(&data.units[id]);
+            self.current.insert(id, state);
+            if self.previous.get(&id).is_some_and(|previous| {
+                previous.hit_points + previous.shields > state.hit_points + state.shields
+            }) {
+                self.damaged.push(id);
+            }
+        }
+    }
+
+    pub(crate) fn previous(&self, id: UnitId) -> Option<&UnitState> {
+        self.previous.get(&id)
+    }
+
+    pub(crate) fn previous_player(&self, id: PlayerId) -> Option<&PlayerState> {
+        self.previous_players.get(id)
+    }
+
+    pub(crate) fn damaged(&self) -> &[UnitId] {
+        &self.damaged
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aimodule::AiModule;
+    use crate::client::Client;
+    use crate::game::Game;
+    use crate::transport::MemoryTransport;
+    use bwapi_wrapper::prelude::*;
+    use bwapi_wrapper::*;
+
+    struct Idle;
+
+    impl AiModule for Idle {
+        fn on_frame(&mut self, _game: &Game) {}
+    }
+
+    fn play_frame(client: &mut Client<MemoryTransport>, type_: BWAPI_EventType_Enum) {
+        let data = client.transport_mut().data_mut();
+        data.frameCount += 1;
+        data.eventCount = 1;
+        data.events[0] = BWAPIC_Event {
+            type_,
+            v1: 3,
+            v2: 0,
+        };
+        client.update(&mut Idle).unwrap();
+    }
+
+    #[test]
+    fn should_report_changes_since_previous_frame() {
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        let data = client.transport_mut().data_mut();
+        data.playerCount = 1;
+        data.players[0].gatheredMinerals = 50;
+        let unit = &mut data.units[3];
+        unit.exists = true;
+        unit.type_ = UnitType::Terran_Barracks as i32;
+        unit.hitPoints = 1000;
+        unit.positionX = 100;
+        unit.order = Order::Nothing as i32;
+        play_frame(&mut client, BWAPI_EventType_Enum::UnitShow);
+        let unit = client.get_game().get_unit(3).unwrap();
+        assert_eq!(unit.get_damage_taken(), 0);
+
+        let data = client.transport_mut().data_mut();
+        data.players[0].gatheredMinerals = 58;
+        let barracks = &mut data.units[3];
+        barracks.hitPoints = 990;
+        barracks.positionX = 104;
+        barracks.order = Order::Train as i32;
+        barracks.trainingQueueCount = 1;
+        play_frame(&mut client, BWAPI_EventType_Enum::MatchFrame);
+
+        let game = client.get_game();
+        assert_eq!(game.units_damaged(), std::slice::from_ref(&unit));
+        assert_eq!(unit.get_damage_taken(), 10);
+        assert!(unit.order_changed());
+        assert!(unit.started_training());
+        assert_eq!(unit.get_position_delta(), Position::new(4, 0));
+        assert_eq!(game.get_player(0).unwrap().get_mineral_income(), 8);
+
+        play_frame(&mut client, BWAPI_EventType_Enum::MatchFrame);
+        assert!(client.get_game().units_damaged().is_empty());
+        assert!(!unit.order_changed());
+    }
+}
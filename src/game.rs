@@ -1,29 +1,40 @@
-use crate::aimodule::AiModule;
 use crate::bullet::Bullet;
 use crate::command::Commands;
+use crate::event::Event;
 use crate::force::Force;
+use crate::frame_diff::{FrameDiff, UnitState};
+#[cfg(feature = "metrics")]
+use crate::metrics::METRICS_FILE;
+#[cfg(feature = "metrics")]
+pub use crate::metrics::RsBwapiMetrics;
+use crate::panic_guard::PanicGuard;
 use crate::player::Player;
 use crate::predicate::IntoPredicate;
 use crate::predicate::Predicate;
 use crate::region::Region;
 use crate::shm::Shm;
+use crate::snapshot::GameSnapshot;
+use crate::transport::SharedGameData;
 use crate::types::c_str_to_str;
 use crate::unit::{Unit, UnitId, UnitInfo};
+use crate::unit_map::{UnitLifecycle, UnitSlots};
+use crate::unit_memory::UnitMemory;
+use crate::units::{UnitIndex, Units};
+use crate::watchdog::Watchdog;
 use crate::*;
 use bwapi_wrapper::*;
-use core::cell::RefCell;
+use core::cell::{Cell, Ref, RefCell};
 #[cfg(feature = "metrics")]
-use metered::{ResponseTime, hdr_histogram::HdrHistogram, measure, time_source::StdInstantMicros};
+use metered::measure;
+#[cfg(feature = "metrics")]
+use metered::metric::{Enter, OnResult};
 use rstar::primitives::Rectangle;
 use rstar::{AABB, Envelope, PointDistance, RTree, RTreeObject};
-use std::ops::Deref;
-use std::rc::Rc;
-
-#[derive(Default, Debug, serde::Serialize)]
-#[cfg(feature = "metrics")]
-pub struct RsBwapiMetrics {
-    frame_time: ResponseTime<RefCell<HdrHistogram>, StdInstantMicros>,
-}
+use std::io;
+use std::ops::{Deref, Range};
+use std::path::Path;
+use std::rc::{Rc, Weak};
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct Game {
@@ -36,6 +47,8 @@ pub struct Game {
 
 pub struct UnitLocation {
     id: UnitId,
+    /// Position in the unit list of the frame
+    pub(crate) index: usize,
     location: Rectangle<[i32; 2]>,
 }
 
@@ -53,17 +66,82 @@ impl PointDistance for UnitLocation {
     }
 }
 
+fn point_segment_distance_2(p: Position, a: Position, b: Position) -> f64 {
+    let (dx, dy) = ((b.x - a.x) as f64, (b.y - a.y) as f64);
+    let length_2 = dx * dx + dy * dy;
+    let t = if length_2 == 0.0 {
+        0.0
+    } else {
+        (((p.x - a.x) as f64 * dx + (p.y - a.y) as f64 * dy) / length_2).clamp(0.0, 1.0)
+    };
+    let (ex, ey) = (
+        a.x as f64 + t * dx - p.x as f64,
+        a.y as f64 + t * dy - p.y as f64,
+    );
+    ex * ex + ey * ey
+}
+
+fn segments_intersect(a: Position, b: Position, c: Position, d: Position) -> bool {
+    let orientation = |p: Position, q: Position, r: Position| {
+        ((q.x - p.x) as i64 * (r.y - p.y) as i64 - (q.y - p.y) as i64 * (r.x - p.x) as i64).signum()
+    };
+    orientation(a, b, c) != orientation(a, b, d) && orientation(c, d, a) != orientation(c, d, b)
+}
+
+/// Squared distance between the segment `a`-`b` and the bounds of a unit.
+fn segment_distance_2(a: Position, b: Position, location: &Rectangle<[i32; 2]>) -> f64 {
+    let ([left, top], [right, bottom]) = (location.lower(), location.upper());
+    let box_distance_2 = |p: Position| {
+        let dx = (left - p.x).max(p.x - right).max(0) as f64;
+        let dy = (top - p.y).max(p.y - bottom).max(0) as f64;
+        dx * dx + dy * dy
+    };
+    let corners = [(left, top), (right, top), (right, bottom), (left, bottom)]
+        .map(|(x, y)| Position { x, y });
+    let crosses = (0..4).any(|i| segments_intersect(a, b, corners[i], corners[(i + 1) % 4]));
+    if crosses {
+        return 0.0;
+    }
+    corners
+        .iter()
+        .map(|&corner| point_segment_distance_2(corner, a, b))
+        .chain([box_distance_2(a), box_distance_2(b)])
+        .fold(f64::MAX, f64::min)
+}
+
+fn polygon_contains(polygon: &[Position], p: Position) -> bool {
+    let mut inside = false;
+    for (i, &a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        if (a.y > p.y) != (b.y > p.y) {
+            let x = a.x as f64 + (p.y - a.y) as f64 * (b.x - a.x) as f64 / (b.y - a.y) as f64;
+            if (p.x as f64) < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
 pub(crate) struct GameInternal {
-    pub(crate) data: Shm<BWAPI_GameData>,
+    pub(crate) data: SharedGameData,
     units: RefCell<Vec<Unit>>,
     pub(crate) unit_infos: RefCell<Vec<UnitInfo>>,
+    pub(crate) lifecycles: RefCell<Vec<UnitLifecycle>>,
+    next_generation: Cell<u32>,
     rtree: RefCell<RTree<UnitLocation>>,
+    unit_index: RefCell<UnitIndex>,
+    pub(crate) frame_diff: RefCell<FrameDiff>,
     pub(crate) cmd: RefCell<Commands>,
     pylons: RefCell<Option<Vec<Unit>>>,
     static_neutrals: RefCell<Vec<Unit>>,
     static_minerals: RefCell<Vec<Unit>>,
     static_geysers: RefCell<Vec<Unit>>,
     visible_units: RefCell<Vec<Unit>>,
+    unit_memory: RefCell<UnitMemory>,
+    watchdog: RefCell<Option<Watchdog>>,
+    /// The `UnitMap`s of the bot, to drop their values of destroyed units
+    unit_maps: RefCell<Vec<Weak<dyn UnitSlots>>>,
 }
 
 impl PositionValidator for &Game {
@@ -77,6 +155,23 @@ impl PositionValidator for &Game {
 
 impl Game {
     pub(crate) fn match_start(&mut self) {
+        // The client might be reused for multiple games, drop everything of the previous one
+        self.inner.unit_infos.borrow_mut().fill(UnitInfo::default());
+        self.inner.lifecycles.borrow_mut().fill(UnitLifecycle {
+            generation: self.next_generation(),
+            ..Default::default()
+        });
+        self.drop_stale_unit_values(0..self.inner.lifecycles.borrow().len());
+        self.inner.static_geysers.borrow_mut().clear();
+        self.inner.static_minerals.borrow_mut().clear();
+        self.inner.static_neutrals.borrow_mut().clear();
+        self.inner.unit_memory.borrow_mut().clear();
+        self.inner.frame_diff.borrow_mut().clear();
+        if let Some(watchdog) = self.inner.watchdog.borrow_mut().as_mut() {
+            watchdog.reset();
+        }
+        #[cfg(feature = "metrics")]
+        self.metrics.clear();
         *self.inner.visible_units.borrow_mut() = (0..self.data.initialUnitCount as usize)
             .filter(|&i| {
                 self.data.units[i].exists && self.data.units[i].type_ != UnitType::Unknown as i32
@@ -105,24 +200,36 @@ impl Game {
         }
     }
 
-    fn refresh(&mut self) {
-        let inner = &self.inner;
-        *inner.units.borrow_mut() = inner.visible_units.borrow().clone();
-        *inner.pylons.borrow_mut() = None;
-        *inner.rtree.borrow_mut() = RTree::bulk_load(
+    pub(crate) fn refresh(&mut self) {
+        measure!(&self.metrics.clone().refresh, {
+            let inner = &self.inner;
             inner
                 .units
-                .borrow()
-                .iter()
-                .map(|u| UnitLocation {
-                    id: u.get_id(),
-                    location: Rectangle::from_corners(
-                        [u.get_left(), u.get_top()],
-                        [u.get_right(), u.get_bottom()],
-                    ),
-                })
-                .collect(),
-        );
+                .borrow_mut()
+                .clone_from(&inner.visible_units.borrow());
+            *inner.pylons.borrow_mut() = None;
+            inner.unit_index.borrow_mut().rebuild(&inner.units.borrow());
+            inner
+                .frame_diff
+                .borrow_mut()
+                .update(&inner.data, &inner.units.borrow());
+            *inner.rtree.borrow_mut() = RTree::bulk_load(
+                inner
+                    .units
+                    .borrow()
+                    .iter()
+                    .enumerate()
+                    .map(|(index, u)| UnitLocation {
+                        id: u.get_id(),
+                        index,
+                        location: Rectangle::from_corners(
+                            [u.get_left(), u.get_top()],
+                            [u.get_right(), u.get_bottom()],
+                        ),
+                    })
+                    .collect(),
+            );
+        })
     }
 
     pub fn get_unit(&self, id: UnitId) -> Option<Unit> {
@@ -149,6 +256,41 @@ impl Game {
     pub fn get_all_units(&self) -> Vec<Unit> {
         self.inner.units.borrow().clone()
     }
+
+    /// The state of `unit` in the previous frame, if it was accessible then.
+    pub fn get_previous_unit_state(&self, unit: &Unit) -> Option<UnitState> {
+        self.inner
+            .frame_diff
+            .borrow()
+            .previous(unit.get_id())
+            .copied()
+    }
+
+    /// Units that lost hit points or shields since the previous frame.
+    pub fn units_damaged(&self) -> Vec<Unit> {
+        self.inner
+            .frame_diff
+            .borrow()
+            .damaged()
+            .iter()
+            .map(|&id| self.get_unit(id).expect("Damaged unit does not exist"))
+            .collect()
+    }
+
+    /// An owned copy of the current frame.
+    pub fn snapshot(&self) -> GameSnapshot {
+        GameSnapshot::new(self)
+    }
+
+    /// All accessible units of the current frame, without copying them.
+    pub fn units(&self) -> Units<'_> {
+        Units {
+            units: self.inner.units.borrow(),
+            rtree: self.inner.rtree.borrow(),
+            index: self.inner.unit_index.borrow(),
+        }
+    }
+
     pub fn get_selected_units(&self) -> Vec<Unit> {
         self.inner
             .data
@@ -243,7 +385,126 @@ impl Game {
             .map(|(ul, _)| self.get_unit(ul.id).expect("Unit from RTree to be present"))
             .find(|u| pred.test(u))
     }
+
+    /// The `k` units closest to `center`, nearest first.
+    pub fn get_k_closest_units<P: Into<Position>, Pred: IntoPredicate<Unit>>(
+        &self,
+        center: P,
+        k: usize,
+        pred: Pred,
+    ) -> Vec<Unit> {
+        let center = center.into();
+        let pred = pred.into_predicate();
+        self.inner
+            .rtree
+            .borrow()
+            .nearest_neighbor_iter(&[center.x, center.y])
+            .map(|ul| self.get_unit(ul.id).expect("Unit from RTree to be present"))
+            .filter(|u| pred.test(u))
+            .take(k)
+            .collect()
+    }
+
+    /// The closest unit of each player that has units matching `pred`.
+    pub fn get_closest_unit_per_player<P: Into<Position>, Pred: IntoPredicate<Unit>>(
+        &self,
+        center: P,
+        pred: Pred,
+    ) -> Vec<Unit> {
+        let center = center.into();
+        let pred = pred.into_predicate();
+        let players = self.inner.unit_index.borrow().player_count();
+        let mut found: Vec<Unit> = vec![];
+        for ul in self
+            .inner
+            .rtree
+            .borrow()
+            .nearest_neighbor_iter(&[center.x, center.y])
+        {
+            let unit = self.get_unit(ul.id).expect("Unit from RTree to be present");
+            let player = unit.get_player();
+            if !found.iter().any(|u| u.get_player() == player) && pred.test(&unit) {
+                found.push(unit);
+                if found.len() == players {
+                    break;
+                }
+            }
+        }
+        found
+    }
+
+    /// Units with bounds within `radius` of the segment from `a` to `b`, ie. the units touched
+    /// by a circle of `radius` moving from `a` to `b`.
+    pub fn get_units_in_capsule<A: Into<Position>, B: Into<Position>, Pred: IntoPredicate<Unit>>(
+        &self,
+        a: A,
+        b: B,
+        radius: i32,
+        pred: Pred,
+    ) -> Vec<Unit> {
+        let (a, b) = (a.into(), b.into());
+        let pred = pred.into_predicate();
+        let radius_2 = (radius * radius) as f64;
+        let envelope = AABB::from_corners(
+            [a.x.min(b.x) - radius, a.y.min(b.y) - radius],
+            [a.x.max(b.x) + radius, a.y.max(b.y) + radius],
+        );
+        self.inner
+            .rtree
+            .borrow()
+            .locate_in_envelope_intersecting(&envelope)
+            .filter(|ul| segment_distance_2(a, b, &ul.location) <= radius_2)
+            .map(|ul| self.get_unit(ul.id).expect("Unit from RTree to be present"))
+            .filter(|u| pred.test(u))
+            .collect()
+    }
+
+    /// Units with bounds intersecting the segment from `a` to `b`.
+    pub fn get_units_on_line<A: Into<Position>, B: Into<Position>, Pred: IntoPredicate<Unit>>(
+        &self,
+        a: A,
+        b: B,
+        pred: Pred,
+    ) -> Vec<Unit> {
+        self.get_units_in_capsule(a, b, 0, pred)
+    }
+
+    /// Units with their center inside the polygon given by its corners.
+    pub fn get_units_in_polygon<Pred: IntoPredicate<Unit>>(
+        &self,
+        polygon: &[Position],
+        pred: Pred,
+    ) -> Vec<Unit> {
+        let pred = pred.into_predicate();
+        let Some(first) = polygon.first() else {
+            return vec![];
+        };
+        let (lt, rb) = polygon.iter().fold((*first, *first), |(lt, rb), p| {
+            (
+                Position::new(lt.x.min(p.x), lt.y.min(p.y)),
+                Position::new(rb.x.max(p.x), rb.y.max(p.y)),
+            )
+        });
+        self.get_units_in_rectangle(lt, rb, |u: &Unit| {
+            polygon_contains(polygon, u.get_position()) && pred.test(u)
+        })
+    }
+
+    /// Units overlapping `rectangle`, see `get_units_in_rectangle`.
+    pub fn get_units_in_area<R: Into<crate::Rectangle<Position>>, Pred: IntoPredicate<Unit>>(
+        &self,
+        rectangle: R,
+        pred: Pred,
+    ) -> Vec<Unit> {
+        let rectangle = rectangle.into();
+        self.get_units_in_rectangle(rectangle.tl, rectangle.br, pred)
+    }
+
     pub fn get_bullets(&self) -> Vec<Bullet> {
+        self.bullets().collect()
+    }
+
+    pub fn bullets(&self) -> impl Iterator<Item = Bullet> + '_ {
         (0..self.data.bullets.len())
             .map(|id| {
                 Bullet::new(
@@ -253,7 +514,6 @@ impl Game {
                 )
             })
             .filter(|b| b.exists())
-            .collect()
     }
 
     pub fn get_region_at<P: Into<Position>>(&self, p: P) -> Option<Region> {
@@ -296,10 +556,8 @@ impl Game {
             panic!("Invalid force id {}", force_id);
         }
         let force_players = self
-            .get_players()
-            .iter()
+            .players()
             .filter(|p| p.force_id() == force_id)
-            .cloned()
             .collect();
         Force::new(
             force_id as usize,
@@ -319,146 +577,195 @@ impl Game {
     }
 
     pub fn get_players(&self) -> Vec<Player> {
-        (0..self.data.playerCount as usize)
-            .map(|i| {
-                Player::new(i, self.clone(), unsafe {
-                    &*(&self.data.players[i] as *const BWAPI_PlayerData)
-                })
+        self.players().collect()
+    }
+
+    pub fn players(&self) -> impl Iterator<Item = Player> + '_ {
+        (0..self.data.playerCount as usize).map(|i| {
+            Player::new(i, self.clone(), unsafe {
+                &*(&self.data.players[i] as *const BWAPI_PlayerData)
             })
-            .collect()
+        })
     }
 
-    pub(crate) fn handle_events(&mut self, module: &mut impl AiModule) {
-        measure!(&self.metrics.clone().frame_time, {
-            let event_count = self.data.eventCount;
-            for i in 0..event_count {
-                let event: BWAPIC_Event = self.inner.data.events[i as usize];
-                use BWAPI_EventType_Enum::*;
-                match event.type_ {
-                    MatchStart => {
-                        self.match_start();
-                        self.refresh();
-                        module.on_start(self);
-                        // // No longer visible after the start event
-                        self.inner.visible_units.borrow_mut().clear();
-                    }
-                    MatchFrame => {
-                        self.refresh();
-                        module.on_frame(self);
-                    }
-                    UnitCreate => {
-                        let id = event.v1 as usize;
-                        self.ensure_unit_info(id);
-                        self.refresh();
-                        module.on_unit_create(
-                            self,
-                            self.get_unit(id).expect("Created unit does not exist"),
-                        );
-                    }
-                    UnitDestroy => {
-                        let id = event.v1 as usize;
-                        self.unit_invisible(id);
-                        self.refresh();
-                        module.on_unit_destroy(
-                            self,
-                            self.get_unit(id).expect("Destroyed unit already removed"),
-                        );
-                    }
-                    UnitDiscover => {
-                        let id = event.v1 as usize;
-                        self.ensure_unit_info(id);
-                        self.refresh();
-                        module.on_unit_discover(
-                            self,
-                            self.get_unit(id).expect("Discovered unit does not exist"),
-                        );
-                    }
-                    UnitEvade => {
-                        let id = event.v1 as usize;
-                        module.on_unit_evade(self, self.get_unit_ex(id));
-                    }
-                    UnitShow => {
-                        let id = event.v1 as usize;
-                        let unit = self.get_unit(id).expect("Shown unit does not exist");
-                        self.inner.visible_units.borrow_mut().push(unit.clone());
-                        self.refresh();
-                        self.ensure_unit_info(id);
-                        module.on_unit_show(self, unit);
-                    }
-                    UnitHide => {
-                        let id = event.v1 as usize;
-                        self.unit_invisible(id);
-                        self.refresh();
-                        module.on_unit_hide(
-                            self,
-                            self.get_unit(id).expect("Hidden unit does not exist"),
-                        );
-                    }
-                    UnitMorph => {
-                        let id = event.v1 as usize;
-                        module.on_unit_morph(
-                            self,
-                            self.get_unit(id).expect("Morphing unit does not exist"),
-                        );
-                    }
-                    UnitRenegade => {
-                        let id = event.v1 as usize;
-                        module.on_unit_morph(
-                            self,
-                            self.get_unit(id).expect("Renegading unit does not exist"),
-                        );
-                    }
-                    UnitComplete => {
-                        let id = event.v1 as usize;
-                        self.ensure_unit_info(id);
-                        self.refresh();
-                        module.on_unit_complete(
-                            self,
-                            self.get_unit(id).expect("Completed unit does not exist"),
-                        );
-                    }
-                    MatchEnd => {
-                        let won = event.v1 != 0;
-                        module.on_end(self, won);
-                    }
-                    MenuFrame => {}
-                    SendText => module.on_send_text(self, self.event_str(event.v1 as usize)),
-                    ReceiveText => {
-                        module.on_receive_text(
-                            self,
-                            self.get_player(event.v1 as usize)
-                                .expect("Text sending player does not exist"),
-                            self.event_str(event.v2 as usize),
-                        );
-                    }
-                    PlayerLeft => {
-                        module.on_player_left(
-                            self,
-                            self.get_player(event.v1 as usize)
-                                .expect("Leaving player does not exist"),
-                        );
-                    }
-                    NukeDetect => {
-                        module.on_nuke_detect(
-                            self,
-                            Position {
-                                x: event.v1,
-                                y: event.v2,
-                            },
-                        );
-                    }
-                    SaveGame => module.on_save_game(self, self.event_str(event.v1 as usize)),
-                    None => {}
-                }
+    /// Whether the current frame is the first one of a new game.
+    pub(crate) fn is_match_start(&self) -> bool {
+        self.raw_events()
+            .iter()
+            .any(|event| event.type_ == BWAPI_EventType_Enum::MatchStart)
+    }
+
+    pub(crate) fn raw_events(&self) -> &[BWAPIC_Event] {
+        &self.data.events[..self.data.eventCount as usize]
+    }
+
+    /// Updates the game state for `event`, before it is passed to the bot.
+    pub(crate) fn begin_event(&mut self, event: BWAPIC_Event) -> Option<Event> {
+        use BWAPI_EventType_Enum::*;
+        let id = event.v1 as usize;
+        Some(match event.type_ {
+            MatchStart => {
+                self.match_start();
+                self.refresh();
+                self.update_unit_memory();
+                Event::MatchStart
+            }
+            MatchFrame => {
+                self.refresh();
+                self.update_unit_memory();
+                Event::MatchFrame
+            }
+            UnitCreate => {
+                self.ensure_unit_info(id);
+                self.refresh();
+                Event::UnitCreate(self.get_unit(id).expect("Created unit does not exist"))
+            }
+            UnitDestroy => {
+                self.unit_invisible(id);
+                self.inner.unit_memory.borrow_mut().forget(id);
+                self.refresh();
+                Event::UnitDestroy(self.get_unit(id).expect("Destroyed unit already removed"))
+            }
+            UnitDiscover => {
+                self.ensure_unit_info(id);
+                self.refresh();
+                Event::UnitDiscover(self.get_unit(id).expect("Discovered unit does not exist"))
+            }
+            UnitEvade => Event::UnitEvade(self.get_unit_ex(id)),
+            UnitShow => {
+                let unit = self.get_unit(id).expect("Shown unit does not exist");
+                self.inner.visible_units.borrow_mut().push(unit.clone());
+                self.refresh();
+                self.ensure_unit_info(id);
+                Event::UnitShow(unit)
+            }
+            UnitHide => {
+                self.unit_invisible(id);
+                self.refresh();
+                Event::UnitHide(self.get_unit(id).expect("Hidden unit does not exist"))
             }
-            let cmd = self.inner.cmd.take();
-            cmd.commit(unsafe { &mut *(self.inner.data.as_ptr() as *mut BWAPI_GameData) });
+            UnitMorph => {
+                self.inner.lifecycles.borrow_mut()[id].morphs += 1;
+                Event::UnitMorph(self.get_unit(id).expect("Morphing unit does not exist"))
+            }
+            UnitRenegade => {
+                self.inner.lifecycles.borrow_mut()[id].renegades += 1;
+                Event::UnitRenegade(self.get_unit(id).expect("Renegading unit does not exist"))
+            }
+            UnitComplete => {
+                self.ensure_unit_info(id);
+                self.refresh();
+                Event::UnitComplete(self.get_unit(id).expect("Completed unit does not exist"))
+            }
+            MatchEnd => Event::MatchEnd {
+                winner: event.v1 != 0,
+            },
+            MenuFrame => Event::MenuFrame,
+            SendText => Event::SendText(self.event_str(event.v1 as usize)),
+            ReceiveText => Event::ReceiveText {
+                player: self
+                    .get_player(event.v1 as usize)
+                    .expect("Text sending player does not exist"),
+                text: self.event_str(event.v2 as usize),
+            },
+            PlayerLeft => Event::PlayerLeft(
+                self.get_player(event.v1 as usize)
+                    .expect("Leaving player does not exist"),
+            ),
+            NukeDetect => Event::NukeDetect(Position {
+                x: event.v1,
+                y: event.v2,
+            }),
+            SaveGame => Event::SaveGame(self.event_str(event.v1 as usize)),
+            None => return Option::None,
         })
     }
+
+    pub(crate) fn register_unit_map(&self, map: Weak<dyn UnitSlots>) {
+        self.inner.unit_maps.borrow_mut().push(map);
+    }
+
+    /// Drops the values of all `UnitMap`s in `ids` which belong to destroyed units.
+    fn drop_stale_unit_values(&self, ids: Range<usize>) {
+        let maps: Vec<_> = {
+            let mut maps = self.inner.unit_maps.borrow_mut();
+            maps.retain(|map| map.strong_count() > 0);
+            maps.iter().filter_map(Weak::upgrade).collect()
+        };
+        let lifecycles = self.inner.lifecycles.borrow()[ids.clone()].to_vec();
+        for map in maps {
+            map.drop_stale(ids.start, &lifecycles);
+        }
+    }
+
+    /// Updates the game state for `event`, after the bot handled it.
+    pub(crate) fn end_event(&mut self, event: BWAPIC_Event) {
+        match event.type_ {
+            BWAPI_EventType_Enum::MatchStart => {
+                // No longer visible after the start event
+                self.inner.visible_units.borrow_mut().clear();
+            }
+            BWAPI_EventType_Enum::UnitDestroy => {
+                // The id may be reused, entries of the destroyed unit are stale from now on
+                let id = event.v1 as usize;
+                self.inner.lifecycles.borrow_mut()[id] = UnitLifecycle {
+                    generation: self.next_generation(),
+                    ..Default::default()
+                };
+                self.drop_stale_unit_values(id..id + 1);
+            }
+            BWAPI_EventType_Enum::MatchEnd => {
+                if let Some(watchdog) = self.inner.watchdog.borrow().as_ref() {
+                    println!("Frame times: {}", watchdog);
+                }
+                #[cfg(feature = "metrics")]
+                if let Err(err) = self.metrics.write_json(METRICS_FILE) {
+                    println!("Could not write {}: {}", METRICS_FILE, err);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    pub(crate) fn commit_commands(&mut self) {
+        let cmd = self.inner.cmd.take();
+        measure!(
+            &self.metrics.commit,
+            cmd.commit(unsafe { &mut *self.inner.data.as_ptr() })
+        );
+    }
+
+    /// Starts handling the events of the current frame: Forgets the panics of the previous game
+    /// and starts the watchdog and the frame time. Must be followed by `end_frame`.
+    pub(crate) fn begin_frame(&self, guard: &mut PanicGuard) -> FrameStart {
+        if self.is_match_start() {
+            guard.reset();
+        }
+        let timed = self.is_in_game();
+        if timed && let Some(watchdog) = self.inner.watchdog.borrow_mut().as_mut() {
+            watchdog.start_frame();
+        }
+        FrameStart {
+            timed,
+            #[cfg(feature = "metrics")]
+            time: self.metrics.frame_time.enter(),
+        }
+    }
+
+    /// Commits the commands of the current frame and stops what `begin_frame` started.
+    pub(crate) fn end_frame(&mut self, start: FrameStart) {
+        self.commit_commands();
+        if start.timed
+            && let Some(watchdog) = self.inner.watchdog.borrow_mut().as_mut()
+        {
+            watchdog.end_frame();
+        }
+        #[cfg(feature = "metrics")]
+        OnResult::<()>::leave_scope(&self.metrics.frame_time, start.time);
+    }
     pub fn get_geysers(&self) -> Vec<Unit> {
-        self.get_all_units()
-            .iter()
-            .filter(|u| u.get_type() == UnitType::Resource_Vespene_Geyser)
+        self.units()
+            .of_type(UnitType::Resource_Vespene_Geyser)
             .cloned()
             .collect()
     }
@@ -472,13 +779,16 @@ impl Game {
         let center = position.into();
         let radius_sq = radius * radius;
         let pred = pred.into_predicate();
-        self.get_units_in_rectangle(
-            (center.x - radius, center.y - radius),
-            (center.x + radius, center.y + radius),
-            |p: &Unit| {
-                let d = center - p.get_position();
-                d.x * d.x + d.y * d.y <= radius_sq && pred.test(p)
-            },
+        measure!(
+            &self.metrics.get_units_in_radius,
+            self.get_units_in_rectangle(
+                (center.x - radius, center.y - radius),
+                (center.x + radius, center.y + radius),
+                |p: &Unit| {
+                    let d = center - p.get_position();
+                    d.x * d.x + d.y * d.y <= radius_sq && pred.test(p)
+                },
+            )
         )
     }
 
@@ -511,11 +821,23 @@ impl Game {
         type_: UnitType,
         check_explored: bool,
     ) -> BwResult<bool> {
-        let builder = builder.into();
+        measure!(
+            &self.metrics.can_build_here,
+            self.check_build_here(builder.into(), position.into(), type_, check_explored)
+        )
+    }
+
+    fn check_build_here(
+        &self,
+        builder: Option<&Unit>,
+        position: TilePosition,
+        type_: UnitType,
+        check_explored: bool,
+    ) -> BwResult<bool> {
         let position = if builder.is_some() && type_.is_addon() {
-            position.into() + TilePosition { x: 4, y: 1 }
+            position + TilePosition { x: 4, y: 1 }
         } else {
-            position.into()
+            position
         };
 
         let lt = position;
@@ -668,21 +990,13 @@ impl Game {
     }
 
     pub fn observers(&self) -> Vec<Player> {
-        self.get_players()
-            .iter()
-            .filter(|p| p.is_observer())
-            .cloned()
-            .collect()
+        self.players().filter(|p| p.is_observer()).collect()
     }
 
     pub fn enemies(&self) -> Vec<Player> {
         let self_ = self.self_();
         if let Some(self_) = self_ {
-            self.get_players()
-                .iter()
-                .filter(|p| p.is_enemy(&self_))
-                .cloned()
-                .collect()
+            self.players().filter(|p| p.is_enemy(&self_)).collect()
         } else {
             vec![]
         }
@@ -877,11 +1191,7 @@ impl Game {
     pub fn allies(&self) -> Vec<Player> {
         let self_ = self.self_();
         if let Some(self_) = self_ {
-            self.get_players()
-                .iter()
-                .filter(|p| p.is_ally(&self_))
-                .cloned()
-                .collect()
+            self.players().filter(|p| p.is_ally(&self_)).collect()
         } else {
             vec![]
         }
@@ -895,15 +1205,62 @@ impl Game {
         self.get_player(self.data.self_ as PlayerId)
     }
 
+    pub(crate) fn set_watchdog(&self, watchdog: Option<Watchdog>) {
+        *self.inner.watchdog.borrow_mut() = watchdog;
+    }
+
+    /// The frame time statistics of the current game, if `Client::set_watchdog` was used.
+    pub fn get_watchdog(&self) -> Option<Watchdog> {
+        self.inner.watchdog.borrow().clone()
+    }
+
+    /// Time left in the current frame before it counts against the limits of the watchdog.
+    /// `None` without a watchdog.
+    pub fn get_remaining_frame_budget(&self) -> Option<Duration> {
+        self.inner
+            .watchdog
+            .borrow()
+            .as_ref()
+            .map(|watchdog| watchdog.get_remaining())
+    }
+
     #[cfg(feature = "metrics")]
     pub fn get_metrics(&self) -> &RsBwapiMetrics {
         &self.metrics
     }
 
+    /// Measures `f` with the timer `name` of the metrics. Without the `metrics` feature, this
+    /// just calls `f`.
+    pub fn time<R>(&self, name: &str, f: impl FnOnce() -> R) -> R {
+        #[cfg(feature = "metrics")]
+        let timer = self.metrics.timer(name);
+        #[cfg(not(feature = "metrics"))]
+        let _ = name;
+        measure!(&*timer, f())
+    }
+
     pub fn is_in_game(&self) -> bool {
         self.data.isInGame
     }
 
+    fn next_generation(&self) -> u32 {
+        let generation = self.inner.next_generation.get() + 1;
+        self.inner.next_generation.set(generation);
+        generation
+    }
+
+    fn update_unit_memory(&self) {
+        self.inner
+            .unit_memory
+            .borrow_mut()
+            .update(self, &self.inner.visible_units.borrow());
+    }
+
+    /// The last known state of all enemy units, including hidden ones.
+    pub fn get_unit_memory(&self) -> Ref<'_, UnitMemory> {
+        self.inner.unit_memory.borrow()
+    }
+
     fn unit_invisible(&self, id: UnitId) {
         let mut visible_units = self.inner.visible_units.borrow_mut();
         let index = visible_units.iter().position(|u| u.get_id() == id);
@@ -1210,34 +1567,201 @@ impl Game {
     }
 }
 
+/// Returned by `Game::begin_frame`.
+pub(crate) struct FrameStart {
+    /// Whether the frame counts for the watchdog
+    timed: bool,
+    #[cfg(feature = "metrics")]
+    time: <crate::metrics::Timer as Enter>::E,
+}
+
 impl Game {
-    pub(crate) fn new(shm: Shm<BWAPI_GameData>) -> Self {
-        let data = unsafe { &*(&*shm as *const BWAPI_GameData) };
+    /// Creates a `Game` from a zlib compressed `BWAPI_GameData` snapshot, like the
+    /// `*_frame0_buffer.bin` files in `resources/test`. The game is initialized as if the match
+    /// just started, which makes it useful for testing map analysis or building placement.
+    ///
+    /// The inflated snapshot has to be exactly one `BWAPI_GameData`, with valid bools, event types
+    /// and counts. Anything else is rejected with `InvalidData`.
+    pub fn from_frame_buffer(compressed: &[u8]) -> io::Result<Self> {
+        let inflated = inflate::inflate_bytes_zlib(compressed)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        crate::validate::game_data(&inflated)?;
+        let shm: Shm<BWAPI_GameData> = Shm::zeroed();
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                inflated.as_ptr(),
+                shm.as_ptr() as *mut u8,
+                inflated.len(),
+            );
+        }
+        let mut game = Game::new(SharedGameData::new(shm));
+        game.match_start();
+        game.refresh();
+        Ok(game)
+    }
+
+    /// Loads a snapshot file, see `from_frame_buffer`.
+    pub fn from_frame_buffer_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_frame_buffer(&std::fs::read(path)?)
+    }
+
+    pub(crate) fn new(shared: SharedGameData) -> Self {
+        // Valid for as long as `inner` keeps the memory alive
+        let data = unsafe { &*(shared.as_ptr() as *const BWAPI_GameData) };
         Game {
             data,
             #[cfg(feature = "metrics")]
             metrics: Default::default(),
             inner: Rc::new(GameInternal {
-                data: shm,
+                data: shared,
                 pylons: RefCell::new(None),
                 rtree: RefCell::new(RTree::new()),
+                unit_index: RefCell::new(UnitIndex::default()),
+                frame_diff: RefCell::new(FrameDiff::default()),
                 cmd: RefCell::new(Commands::new()),
                 static_geysers: RefCell::new(vec![]),
                 static_minerals: RefCell::new(vec![]),
                 static_neutrals: RefCell::new(vec![]),
-                unit_infos: RefCell::new(vec![
-                    UnitInfo {
-                        initial_hit_points: 0,
-                        initial_resources: 0,
-                        initial_position: Position::new(-1, -1),
-                        initial_type: UnitType::None,
-                        last_command_frame: -10
-                    };
-                    10000
-                ]),
+                unit_infos: RefCell::new(vec![UnitInfo::default(); 10000]),
+                lifecycles: RefCell::new(vec![UnitLifecycle::default(); 10000]),
+                next_generation: Cell::new(0),
                 visible_units: RefCell::new(vec![]),
+                unit_memory: RefCell::new(UnitMemory::default()),
                 units: RefCell::new(vec![]),
+                watchdog: RefCell::new(None),
+                unit_maps: RefCell::new(vec![]),
             }),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::aimodule::AiModule;
+    use crate::client::Client;
+    use crate::transport::MemoryTransport;
+
+    struct Idle;
+
+    impl AiModule for Idle {
+        fn on_frame(&mut self, _game: &Game) {}
+    }
+
+    /// Marines of player 0 at x = 100, 200, 300 and of player 1 at x = 400, all at y = 100.
+    fn client_with_marines() -> Client<MemoryTransport> {
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        let data = client.transport_mut().data_mut();
+        data.playerCount = 2;
+        data.frameCount = 1;
+        data.eventCount = 4;
+        for id in 1..=4 {
+            let unit = &mut data.units[id];
+            unit.exists = true;
+            unit.player = (id / 4) as i32;
+            unit.type_ = UnitType::Terran_Marine as i32;
+            unit.positionX = id as i32 * 100;
+            unit.positionY = 100;
+            data.events[id - 1] = BWAPIC_Event {
+                type_: BWAPI_EventType_Enum::UnitShow,
+                v1: id as i32,
+                v2: 0,
+            };
+        }
+        client.update(&mut Idle).unwrap();
+        client
+    }
+
+    /// Wraps `bytes` in a zlib stream of uncompressed blocks.
+    fn stored_zlib(bytes: &[u8]) -> Vec<u8> {
+        let mut out = vec![0x78, 0x01];
+        let mut chunks = bytes.chunks(u16::MAX as usize).peekable();
+        while let Some(chunk) = chunks.next() {
+            out.push(chunks.peek().is_none() as u8);
+            out.extend((chunk.len() as u16).to_le_bytes());
+            out.extend((!(chunk.len() as u16)).to_le_bytes());
+            out.extend(chunk);
+        }
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in bytes {
+            a = (a + byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        out.extend(((b << 16) | a).to_be_bytes());
+        out
+    }
+
+    fn ids(units: Vec<Unit>) -> Vec<UnitId> {
+        let mut ids: Vec<_> = units.iter().map(|u| u.get_id()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn should_measure_segment_distance_to_bounds() {
+        let bounds = Rectangle::from_corners([0, 0], [10, 10]);
+        let p = |x, y| Position { x, y };
+        assert_eq!(segment_distance_2(p(-5, 5), p(15, 5), &bounds), 0.0);
+        assert_eq!(segment_distance_2(p(-5, -5), p(15, -5), &bounds), 25.0);
+        assert_eq!(segment_distance_2(p(13, 14), p(13, 14), &bounds), 25.0);
+        assert!(polygon_contains(&[p(0, 0), p(10, 0), p(0, 10)], p(2, 2)));
+        assert!(!polygon_contains(&[p(0, 0), p(10, 0), p(0, 10)], p(8, 8)));
+    }
+
+    #[test]
+    fn should_find_units_with_extended_spatial_queries() {
+        let client = client_with_marines();
+        let game = client.get_game();
+        let all = |_: &Unit| true;
+
+        assert_eq!(
+            game.get_k_closest_units((190, 100), 2, all)
+                .iter()
+                .map(|u| u.get_id())
+                .collect::<Vec<_>>(),
+            [2, 1]
+        );
+        assert_eq!(ids(game.get_units_on_line((150, 0), (250, 200), all)), [2]);
+        assert_eq!(
+            ids(game.get_units_in_capsule((100, 150), (300, 150), 40, all)),
+            [1, 2, 3]
+        );
+        assert_eq!(
+            ids(game.get_units_in_polygon(
+                &[
+                    Position::new(150, 0),
+                    Position::new(450, 0),
+                    Position::new(450, 200)
+                ],
+                all
+            )),
+            [3, 4]
+        );
+        assert_eq!(
+            ids(
+                game.get_units_in_area(crate::Rectangle::new((50, 50), (250, 150)), |u: &Unit| u
+                    .get_id()
+                    != 1)
+            ),
+            [2]
+        );
+        assert_eq!(ids(game.get_closest_unit_per_player((0, 0), all)), [1, 4]);
+    }
+
+    #[test]
+    fn should_only_load_frame_buffers_of_valid_game_data() {
+        let size = std::mem::size_of::<BWAPI_GameData>();
+        let mut bytes = vec![0; size + 1];
+        assert!(Game::from_frame_buffer(&stored_zlib(&bytes)).is_err());
+        assert!(Game::from_frame_buffer(&stored_zlib(&bytes[..size - 1])).is_err());
+
+        let exists = std::mem::offset_of!(BWAPI_GameData, units)
+            + std::mem::offset_of!(BWAPI_UnitData, exists);
+        bytes[exists] = 2;
+        assert!(Game::from_frame_buffer(&stored_zlib(&bytes[..size])).is_err());
+
+        bytes[exists] = 1;
+        let game = Game::from_frame_buffer(&stored_zlib(&bytes[..size])).unwrap();
+        assert_eq!(game.get_frame_count(), 0);
+    }
+}
@@ -6,43 +6,96 @@ macro_rules! measure {
 }
 
 // mod bwem;
+#[cfg(any(feature = "recording", feature = "bridge"))]
+mod delta;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 mod projected;
 mod shm;
+mod validate;
 
 pub use crate::types::*;
 pub use bwapi_wrapper::prelude::*;
 pub mod aimodule;
+#[cfg(feature = "bridge")]
+pub mod bridge;
+pub mod builder;
 pub mod bullet;
 pub mod can_do;
 pub mod client;
 pub mod command;
+pub mod event;
 pub mod force;
+pub mod frame_diff;
 pub mod game;
+pub mod panic_guard;
 pub mod player;
 pub mod predicate;
+#[cfg(feature = "recording")]
+pub mod recording;
 pub mod region;
 pub mod sma;
+pub mod snapshot;
+pub mod transport;
 pub mod types;
 pub mod unit;
+pub mod unit_map;
+pub mod unit_memory;
+pub mod units;
+pub mod watchdog;
 
-pub use aimodule::AiModule;
+pub use aimodule::{AiModule, ModuleList};
 pub use bullet::{Bullet, BulletType};
+pub use event::{Event, Frame};
 pub use force::Force;
+pub use frame_diff::UnitState;
 pub use game::Game;
+pub use panic_guard::{PanicGuard, PanicPolicy};
 pub use player::{Player, PlayerId};
-pub use unit::{Unit, UnitId};
+pub use snapshot::GameSnapshot;
+pub use unit::{Unit, UnitId, WeakUnit};
+pub use unit_map::UnitMap;
+pub use unit_memory::{RememberedUnit, UnitMemory};
+pub use units::Units;
+pub use watchdog::{FrameTimeLimit, Watchdog};
+
+/// Connects to BWAPI and plays games until the server is closed. `build_module` is called at the
+/// start of every game, with the module of the previous game if there was one.
+pub fn start_session<M: AiModule>(build_module: impl FnMut(&Game, Option<M>) -> M) {
+    let mut client = client::Client::default();
+
+    println!("Waiting for games");
+    if let Err(err) = client.run_session(build_module) {
+        println!("{}", err);
+    }
+}
 
 pub fn start<M: AiModule>(build_module: impl FnOnce(&Game) -> M) {
+    start_with_panic_policy(PanicPolicy::Propagate, build_module);
+}
+
+/// Like `start`, but panics of the module are handled according to `policy`.
+pub fn start_with_panic_policy<M: AiModule>(
+    policy: PanicPolicy,
+    build_module: impl FnOnce(&Game) -> M,
+) {
     let mut client = client::Client::default();
+    client.set_panic_policy(policy);
 
     println!("Waiting for frame to start");
     let mut module = Box::new(build_module(client.get_game()));
 
     while !client.get_game().is_in_game() {
-        client.update(&mut *module);
+        if let Err(err) = client.update(&mut *module) {
+            println!("{}", err);
+            return;
+        }
     }
 
     while client.get_game().is_in_game() {
-        client.update(&mut *module);
+        if let Err(err) = client.update(&mut *module) {
+            println!("{}", err);
+            return;
+        }
     }
 }
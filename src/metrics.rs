@@ -0,0 +1,127 @@
+use metered::clear::Clear;
+use metered::{ResponseTime, hdr_histogram::HdrHistogram, time_source::StdInstantMicros};
+use serde::{Serialize, Serializer};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Where the metrics are written at the end of each game, each game overwrites the previous one
+pub const METRICS_FILE: &str = "bwapi-data/write/rsbwapi_metrics.json";
+
+pub type Timer = ResponseTime<RefCell<HdrHistogram>, StdInstantMicros>;
+
+/// Response times in microseconds of the `AiModule` callbacks, internal steps of rsbwapi,
+/// expensive queries and the timers of `Game::time`. Cleared at the start of each game.
+#[derive(Default, Debug, Serialize)]
+pub struct RsBwapiMetrics {
+    pub(crate) frame_time: Timer,
+    pub(crate) on_end: Timer,
+    pub(crate) on_nuke_detect: Timer,
+    pub(crate) on_player_left: Timer,
+    pub(crate) on_receive_text: Timer,
+    pub(crate) on_save_game: Timer,
+    pub(crate) on_send_text: Timer,
+    pub(crate) on_start: Timer,
+    pub(crate) on_frame: Timer,
+    pub(crate) on_unit_create: Timer,
+    pub(crate) on_unit_destroy: Timer,
+    pub(crate) on_unit_discover: Timer,
+    pub(crate) on_unit_complete: Timer,
+    pub(crate) on_unit_evade: Timer,
+    pub(crate) on_unit_hide: Timer,
+    pub(crate) on_unit_morph: Timer,
+    pub(crate) on_unit_renegade: Timer,
+    pub(crate) on_unit_show: Timer,
+    /// Updating units and the rtree
+    pub(crate) refresh: Timer,
+    /// Writing the commands of a frame into the game data
+    pub(crate) commit: Timer,
+    pub(crate) can_build_here: Timer,
+    pub(crate) get_units_in_radius: Timer,
+    #[serde(serialize_with = "serialize_timers")]
+    timers: RefCell<BTreeMap<String, Rc<Timer>>>,
+}
+
+fn serialize_timers<S: Serializer>(
+    timers: &RefCell<BTreeMap<String, Rc<Timer>>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(timers.borrow().iter().map(|(name, timer)| (name, &**timer)))
+}
+
+impl RsBwapiMetrics {
+    /// The user defined timer `name`, created on first use.
+    pub(crate) fn timer(&self, name: &str) -> Rc<Timer> {
+        if let Some(timer) = self.timers.borrow().get(name) {
+            return timer.clone();
+        }
+        self.timers
+            .borrow_mut()
+            .entry(name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Forgets the response times of the previous game.
+    pub(crate) fn clear(&self) {
+        for timer in [
+            &self.frame_time,
+            &self.on_end,
+            &self.on_nuke_detect,
+            &self.on_player_left,
+            &self.on_receive_text,
+            &self.on_save_game,
+            &self.on_send_text,
+            &self.on_start,
+            &self.on_frame,
+            &self.on_unit_create,
+            &self.on_unit_destroy,
+            &self.on_unit_discover,
+            &self.on_unit_complete,
+            &self.on_unit_evade,
+            &self.on_unit_hide,
+            &self.on_unit_morph,
+            &self.on_unit_renegade,
+            &self.on_unit_show,
+            &self.refresh,
+            &self.commit,
+            &self.can_build_here,
+            &self.get_units_in_radius,
+        ] {
+            timer.clear();
+        }
+        self.timers.borrow_mut().clear();
+    }
+
+    /// Writes all response times as JSON to `path`.
+    pub fn write_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        serde_json::to_writer_pretty(File::create(path)?, self).map_err(io::Error::from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use metered::measure;
+
+    #[test]
+    fn should_forget_response_times_when_cleared() {
+        let metrics = RsBwapiMetrics::default();
+        measure!(&metrics.frame_time, ());
+        measure!(&*metrics.timer("search"), ());
+        measure!(&*metrics.timer("search"), ());
+        assert_eq!(metrics.timer("search").0.borrow().len(), 2);
+
+        metrics.clear();
+
+        assert_eq!(metrics.frame_time.0.borrow().len(), 0);
+        assert!(metrics.timers.borrow().is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+use crate::game::Game;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+
+/// What to do when an `AiModule` callback panics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Don't catch panics, they end the bot process
+    #[default]
+    Propagate,
+    /// Log the panic and keep calling the module
+    Continue,
+    /// Stop calling the module for the rest of the game once it panicked this often
+    DisableAfter(u32),
+    /// Leave the game after the first panic
+    LeaveGame,
+}
+
+/// Catches panics of `AiModule` callbacks according to a `PanicPolicy`. Commands issued before
+/// the panic are still sent to the server.
+#[derive(Debug, Default)]
+pub struct PanicGuard {
+    policy: PanicPolicy,
+    panics: u32,
+    disabled: bool,
+}
+
+fn payload_str(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "<unknown payload>"
+    }
+}
+
+impl PanicGuard {
+    pub fn new(policy: PanicPolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    pub fn policy(&self) -> PanicPolicy {
+        self.policy
+    }
+
+    /// Number of panics caught in the current game.
+    pub fn panics(&self) -> u32 {
+        self.panics
+    }
+
+    /// Whether callbacks are skipped because of previous panics.
+    pub fn is_disabled(&self) -> bool {
+        self.disabled
+    }
+
+    /// Forgets the panics of the previous game.
+    pub(crate) fn reset(&mut self) {
+        self.panics = 0;
+        self.disabled = false;
+    }
+
+    pub(crate) fn call(&mut self, game: &Game, callback: impl FnOnce()) {
+        if self.disabled {
+            return;
+        }
+        if self.policy == PanicPolicy::Propagate {
+            callback();
+            return;
+        }
+        let Err(payload) = panic::catch_unwind(AssertUnwindSafe(callback)) else {
+            return;
+        };
+        self.panics += 1;
+        println!(
+            "AiModule panicked in frame {}: {}",
+            game.get_frame_count(),
+            payload_str(payload.as_ref())
+        );
+        match self.policy {
+            PanicPolicy::DisableAfter(limit) if self.panics >= limit => {
+                println!("Disabling AiModule after {} panics", self.panics);
+                self.disabled = true;
+            }
+            PanicPolicy::LeaveGame => {
+                game.leave_game();
+                self.disabled = true;
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::aimodule::AiModule;
+    use crate::client::Client;
+    use crate::transport::MemoryTransport;
+    use bwapi_wrapper::*;
+
+    struct Panicking(u32);
+
+    impl AiModule for Panicking {
+        fn on_frame(&mut self, game: &Game) {
+            self.0 += 1;
+            game.send_text("before panic");
+            panic!("on_frame failed");
+        }
+    }
+
+    fn play_frames(client: &mut Client<MemoryTransport>, module: &mut Panicking, frames: i32) {
+        for frame in 1..=frames {
+            let data = client.transport_mut().data_mut();
+            data.frameCount = frame;
+            data.commandCount = 0;
+            data.eventCount = 1;
+            data.events[0].type_ = BWAPI_EventType_Enum::MatchFrame;
+            client.update(module).unwrap();
+        }
+    }
+
+    #[test]
+    fn should_disable_module_after_panics() {
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        client.set_panic_policy(PanicPolicy::DisableAfter(2));
+        let mut module = Panicking(0);
+
+        play_frames(&mut client, &mut module, 3);
+
+        assert_eq!(module.0, 2);
+        assert!(client.panic_guard().is_disabled());
+    }
+
+    #[test]
+    fn should_commit_commands_and_leave_game_on_panic() {
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        client.set_panic_policy(PanicPolicy::LeaveGame);
+        let mut module = Panicking(0);
+
+        play_frames(&mut client, &mut module, 1);
+
+        let data = client.transport().data();
+        let commands: Vec<_> = data.commands[..data.commandCount as usize]
+            .iter()
+            .map(|command| command.type_)
+            .collect();
+        // The text was queued before the panic
+        assert!(commands.contains(&BWAPIC_CommandType_Enum::SendText));
+        assert!(commands.contains(&BWAPIC_CommandType_Enum::LeaveGame));
+        assert_eq!(client.panic_guard().panics(), 1);
+    }
+}
@@ -1,7 +1,8 @@
 use crate::force::Force;
+use crate::frame_diff::PlayerState;
 use crate::game::Game;
-use crate::types::c_str_to_str;
 use crate::types::Color;
+use crate::types::c_str_to_str;
 use crate::unit::Unit;
 use bwapi_wrapper::prelude::*;
 use bwapi_wrapper::*;
@@ -69,6 +70,40 @@ impl Player {
         self.data.gatheredMinerals
     }
 
+    fn previous_state(&self) -> Option<PlayerState> {
+        self.game
+            .inner
+            .frame_diff
+            .borrow()
+            .previous_player(self.id)
+            .copied()
+    }
+
+    /// Minerals gathered since the previous frame.
+    pub fn get_mineral_income(&self) -> i32 {
+        self.previous_state().map_or(0, |previous| {
+            self.data.gatheredMinerals - previous.gathered_minerals
+        })
+    }
+
+    /// Gas gathered since the previous frame.
+    pub fn get_gas_income(&self) -> i32 {
+        self.previous_state()
+            .map_or(0, |previous| self.data.gatheredGas - previous.gathered_gas)
+    }
+
+    /// Change of the minerals since the previous frame, including spending.
+    pub fn get_minerals_delta(&self) -> i32 {
+        self.previous_state()
+            .map_or(0, |previous| self.data.minerals - previous.minerals)
+    }
+
+    /// Change of the gas since the previous frame, including spending.
+    pub fn get_gas_delta(&self) -> i32 {
+        self.previous_state()
+            .map_or(0, |previous| self.data.gas - previous.gas)
+    }
+
     pub fn get_building_score(&self) -> i32 {
         self.data.totalBuildingScore
     }
@@ -110,12 +145,7 @@ impl Player {
     }
 
     pub fn get_units(&self) -> Vec<Unit> {
-        self.game
-            .get_all_units()
-            .iter()
-            .filter(|u| u.get_player() == *self)
-            .cloned()
-            .collect()
+        self.game.units().of_player(self.id).cloned().collect()
     }
 
     pub fn get_upgrade_level(&self, upgrade_type: UpgradeType) -> i32 {
@@ -0,0 +1,327 @@
+//! Recording of games frame by frame, to replay them later without StarCraft.
+//!
+//! File layout, all numbers little endian:
+//! * Header: `RSBWREC`, format version `u8`, `size_of::<BWAPI_GameData>()` as `u32`
+//! * Per frame: the length of the game data delta as `u32` followed by the delta to the previous
+//!   frame's game data. Frame count and events are part of the game data.
+use crate::aimodule::AiModule;
+use crate::client::{Client, ClientError};
+use crate::delta::{self, read_u32};
+use crate::game::Game;
+use crate::shm::Shm;
+use crate::transport::{MemoryTransport, SharedGameData, Transport};
+use crate::types::c_str_to_str;
+use bwapi_wrapper::prelude::UnitCommand;
+use bwapi_wrapper::*;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 7] = b"RSBWREC";
+const VERSION: u8 = 2;
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Writes `BWAPI_GameData` frames into a recording.
+pub struct FrameRecorder<W: Write> {
+    out: W,
+    previous: Vec<u8>,
+}
+
+impl FrameRecorder<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> FrameRecorder<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        let size = std::mem::size_of::<BWAPI_GameData>();
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        out.write_all(&(size as u32).to_le_bytes())?;
+        Ok(Self {
+            out,
+            previous: vec![0; size],
+        })
+    }
+
+    pub fn record(&mut self, data: &BWAPI_GameData) -> io::Result<()> {
+        let delta = delta::encode(&mut self.previous, delta::as_bytes(data))?;
+        self.out.write_all(&(delta.len() as u32).to_le_bytes())?;
+        self.out.write_all(&delta)
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+pub struct RecordedFrame {
+    pub frame_count: i32,
+    pub events: Vec<BWAPIC_Event>,
+}
+
+/// Reads the frames of a recording created by a `FrameRecorder`.
+pub struct FrameReader<R: Read> {
+    input: R,
+    /// The game data as read from the recording, before it was validated
+    raw: Vec<u8>,
+    data: Shm<BWAPI_GameData>,
+}
+
+impl FrameReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> FrameReader<R> {
+    pub fn new(mut input: R) -> io::Result<Self> {
+        let mut magic = [0; 7];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("Not a recording"));
+        }
+        let mut version = [0];
+        input.read_exact(&mut version)?;
+        if version[0] != VERSION {
+            return Err(invalid_data("Unsupported recording version"));
+        }
+        let size = std::mem::size_of::<BWAPI_GameData>();
+        if read_u32(&mut input)? as usize != size {
+            return Err(invalid_data(
+                "Recording was made with a different BWAPI_GameData layout",
+            ));
+        }
+        Ok(Self {
+            input,
+            raw: vec![0; size],
+            data: Shm::zeroed(),
+        })
+    }
+
+    /// Reads the next frame, `None` at the end of the recording. Afterwards, `data` contains the
+    /// game data of that frame.
+    pub fn next_frame(&mut self) -> io::Result<Option<RecordedFrame>> {
+        let len = match read_u32(&mut self.input) {
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result? as usize,
+        };
+        if len > delta::max_encoded_len(self.raw.len()) {
+            return Err(invalid_data("Frame is larger than the game data"));
+        }
+        let mut delta = vec![0; len];
+        self.input.read_exact(&mut delta)?;
+        delta::decode_game_data(&mut self.raw, &mut self.data, &delta)?;
+        Ok(Some(RecordedFrame {
+            frame_count: self.data.frameCount,
+            events: self.data.events[..self.data.eventCount as usize].to_vec(),
+        }))
+    }
+
+    pub fn data(&self) -> &BWAPI_GameData {
+        &self.data
+    }
+}
+
+/// Records every frame received by the wrapped transport, as a drop-in for `Client::new`. Each
+/// recorded frame corresponds to one `Client::update`.
+pub struct RecordingTransport<T: Transport, W: Write> {
+    inner: T,
+    recorder: FrameRecorder<W>,
+    data: Option<SharedGameData>,
+}
+
+impl<T: Transport, W: Write> RecordingTransport<T, W> {
+    pub fn new(inner: T, recorder: FrameRecorder<W>) -> Self {
+        Self {
+            inner,
+            recorder,
+            data: None,
+        }
+    }
+
+    pub fn into_inner(self) -> (T, FrameRecorder<W>) {
+        (self.inner, self.recorder)
+    }
+}
+
+// Only reads the game data, all writes are done by `inner`.
+unsafe impl<T: Transport, W: Write> Transport for RecordingTransport<T, W> {
+    fn connect(&mut self) -> Result<SharedGameData, ClientError> {
+        let data = self.inner.connect()?;
+        self.data = Some(data.clone());
+        Ok(data)
+    }
+
+    fn wait_for_frame(&mut self) -> Result<(), ClientError> {
+        self.inner.wait_for_frame()?;
+        let data = self.data.as_ref().expect("Transport is not connected");
+        self.recorder.record(data)?;
+        Ok(())
+    }
+
+    fn signal_done(&mut self) -> Result<(), ClientError> {
+        self.inner.signal_done()
+    }
+}
+
+/// Everything a bot committed to the server in one frame.
+pub struct CommittedFrame {
+    pub frame_count: i32,
+    pub game_commands: Vec<BWAPIC_Command>,
+    pub unit_commands: Vec<UnitCommand>,
+    pub shapes: Vec<BWAPIC_Shape>,
+    pub strings: Vec<String>,
+}
+
+/// Plays a recording into an `AiModule`, as if it were a live game.
+pub struct PlaybackClient<R: Read> {
+    reader: FrameReader<R>,
+    client: Client<MemoryTransport>,
+}
+
+impl PlaybackClient<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(FrameReader::open(path)?))
+    }
+}
+
+impl<R: Read> PlaybackClient<R> {
+    pub fn new(reader: FrameReader<R>) -> Self {
+        Self {
+            reader,
+            client: Client::new(MemoryTransport::default())
+                .expect("Memory transport can always connect"),
+        }
+    }
+
+    pub fn get_game(&self) -> &Game {
+        self.client.get_game()
+    }
+
+    /// Plays the next recorded frame. Returns what `module` committed, or `None` at the end of
+    /// the recording.
+    pub fn step(
+        &mut self,
+        module: &mut impl AiModule,
+    ) -> Result<Option<CommittedFrame>, ClientError> {
+        if self.reader.next_frame()?.is_none() {
+            return Ok(None);
+        }
+        let data = self.client.transport_mut().data_mut();
+        // Too large for the stack, and the reader's data was validated
+        unsafe { std::ptr::copy_nonoverlapping(self.reader.data(), data, 1) };
+        let commands = data.commandCount as usize;
+        let unit_commands = data.unitCommandCount as usize;
+        let shapes = data.shapeCount as usize;
+        let strings = data.stringCount as usize;
+
+        self.client.update(module)?;
+
+        let data = self.client.transport().data();
+        Ok(Some(CommittedFrame {
+            frame_count: data.frameCount,
+            game_commands: data.commands[commands..data.commandCount as usize].to_vec(),
+            unit_commands: data.unitCommands[unit_commands..data.unitCommandCount as usize]
+                .to_vec(),
+            shapes: data.shapes[shapes..data.shapeCount as usize].to_vec(),
+            strings: data.strings[strings..data.stringCount as usize]
+                .iter()
+                .map(|string| c_str_to_str(string))
+                .collect(),
+        }))
+    }
+
+    /// Plays the remaining recording.
+    pub fn run(&mut self, module: &mut impl AiModule) -> Result<Vec<CommittedFrame>, ClientError> {
+        let mut frames = vec![];
+        while let Some(frame) = self.step(module)? {
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_read_recorded_frames() {
+        let mut data: Shm<BWAPI_GameData> = Shm::zeroed();
+        let mut recorder = FrameRecorder::new(vec![]).unwrap();
+        for frame in 1..=2 {
+            data.frameCount = frame;
+            data.eventCount = 1;
+            data.events[0].type_ = BWAPI_EventType_Enum::MatchFrame;
+            data.events[0].v1 = frame * 10;
+            recorder.record(&data).unwrap();
+        }
+        let recording = recorder.into_inner().unwrap();
+
+        let mut reader = FrameReader::new(recording.as_slice()).unwrap();
+        for frame in 1..=2 {
+            let recorded = reader.next_frame().unwrap().unwrap();
+            assert_eq!(recorded.frame_count, frame);
+            assert_eq!(recorded.events.len(), 1);
+            assert_eq!(recorded.events[0].v1, frame * 10);
+            assert_eq!(reader.data().frameCount, frame);
+        }
+        assert!(reader.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn should_reject_frames_with_unknown_event_types() {
+        let mut frame = vec![0; std::mem::size_of::<BWAPI_GameData>()];
+        frame[std::mem::offset_of!(BWAPI_GameData, events)..][..4]
+            .copy_from_slice(&u32::MAX.to_ne_bytes());
+        let delta = delta::encode(&mut vec![0; frame.len()], &frame).unwrap();
+        let mut recording = FrameRecorder::new(vec![]).unwrap().into_inner().unwrap();
+        recording.extend((delta.len() as u32).to_le_bytes());
+        recording.extend(delta);
+
+        let mut reader = FrameReader::new(recording.as_slice()).unwrap();
+
+        let err = reader.next_frame().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            reader.data().events[0].type_,
+            BWAPI_EventType_Enum::MatchStart
+        );
+    }
+
+    struct Chatty;
+
+    impl AiModule for Chatty {
+        fn on_frame(&mut self, game: &Game) {
+            game.send_text(&format!("frame {}", game.get_frame_count()));
+        }
+    }
+
+    #[test]
+    fn should_collect_committed_commands_in_playback() {
+        let mut data: Shm<BWAPI_GameData> = Shm::zeroed();
+        let mut recorder = FrameRecorder::new(vec![]).unwrap();
+        for frame in 1..=3 {
+            data.frameCount = frame;
+            data.eventCount = 1;
+            data.events[0].type_ = BWAPI_EventType_Enum::MatchFrame;
+            recorder.record(&data).unwrap();
+        }
+        let recording = recorder.into_inner().unwrap();
+
+        let mut playback = PlaybackClient::new(FrameReader::new(recording.as_slice()).unwrap());
+        let frames = playback.run(&mut Chatty).unwrap();
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[2].frame_count, 3);
+        assert_eq!(frames[2].strings, ["frame 3"]);
+        assert_eq!(frames[2].game_commands.len(), 1);
+    }
+}
@@ -1,24 +1,33 @@
 use core::ops::{Deref, DerefMut};
 use core::ptr::NonNull;
 
-#[cfg(windows)]
+use std::alloc::{Layout, alloc_zeroed, dealloc, handle_alloc_error};
 use std::ffi::CString;
 #[cfg(windows)]
 use winapi::shared::minwindef::FALSE;
 #[cfg(windows)]
 use winapi::um::handleapi::CloseHandle;
 #[cfg(windows)]
-use winapi::um::memoryapi::{MapViewOfFile, FILE_MAP_READ, FILE_MAP_WRITE};
+use winapi::um::memoryapi::{
+    FILE_MAP_READ, FILE_MAP_WRITE, MapViewOfFile, UnmapViewOfFile, VirtualQuery,
+};
 #[cfg(windows)]
 use winapi::um::winbase::OpenFileMappingA;
 #[cfg(windows)]
-use winapi::um::winnt::HANDLE;
+use winapi::um::winnt::{HANDLE, MEMORY_BASIC_INFORMATION};
 
-#[cfg(windows)]
-pub(crate) struct Shm<T: ?Sized>(HANDLE, NonNull<T>);
+pub(crate) struct Shm<T>(Mapping, NonNull<T>);
 
-#[cfg(not(windows))]
-pub(crate) struct Shm<T: ?Sized>((), NonNull<T>);
+/// Who is responsible for releasing the memory behind a `Shm`.
+pub(crate) enum Mapping {
+    #[cfg(windows)]
+    File(HANDLE),
+    /// Length of the region mapped with `mmap`
+    #[cfg(unix)]
+    Mmap(usize),
+    /// Allocated with `std::alloc`
+    Heap,
+}
 
 impl<T> Deref for Shm<T> {
     type Target = T;
@@ -44,17 +53,32 @@ impl<T> Shm<T> {
         unsafe { self.1.as_ref() }
     }
 
-    #[cfg(test)]
-    pub fn from_mut_slice(data: &mut [u8]) -> Shm<T> {
-        Self((), NonNull::new(data.as_mut_ptr()).unwrap().cast())
+    /// Allocates a zeroed `T` on the heap. Only use for `T`s where all zero bytes are valid, like
+    /// the BWAPI structs.
+    pub(crate) fn zeroed() -> Shm<T> {
+        let layout = Layout::new::<T>();
+        let ptr = unsafe { alloc_zeroed(layout) } as *mut T;
+        let Some(ptr) = NonNull::new(ptr) else {
+            handle_alloc_error(layout)
+        };
+        Self(Mapping::Heap, ptr)
     }
 }
 
-#[cfg(windows)]
-impl<T: ?Sized> Drop for Shm<T> {
+impl<T> Drop for Shm<T> {
     fn drop(&mut self) {
-        unsafe {
-            CloseHandle(self.0);
+        match self.0 {
+            #[cfg(windows)]
+            Mapping::File(handle) => unsafe {
+                CloseHandle(handle);
+            },
+            #[cfg(unix)]
+            Mapping::Mmap(len) => unsafe {
+                libc::munmap(self.1.as_ptr() as *mut libc::c_void, len);
+            },
+            Mapping::Heap => unsafe {
+                dealloc(self.1.as_ptr() as *mut u8, Layout::new::<T>());
+            },
         }
     }
 }
@@ -71,11 +95,133 @@ pub(crate) fn map_memory<T>(name: &str) -> Option<Shm<T>> {
         }
         let mapped =
             MapViewOfFile(handle, FILE_MAP_READ | FILE_MAP_WRITE, 0, 0, memory_size) as *mut T;
-        Some(Shm(handle, NonNull::new_unchecked(mapped)))
+        Some(Shm(Mapping::File(handle), NonNull::new_unchecked(mapped)))
+    }
+}
+
+/// Size of the named shared memory, rounded up to whole pages.
+#[cfg(windows)]
+pub(crate) fn memory_size(name: &str) -> Option<usize> {
+    let lp_name = CString::new(name).unwrap();
+    unsafe {
+        let handle = OpenFileMappingA(FILE_MAP_READ, FALSE, lp_name.as_ptr());
+        if handle.is_null() {
+            return None;
+        }
+        let view = MapViewOfFile(handle, FILE_MAP_READ, 0, 0, 0);
+        let mut info: MEMORY_BASIC_INFORMATION = std::mem::zeroed();
+        let size = if !view.is_null()
+            && VirtualQuery(view, &mut info, std::mem::size_of_val(&info)) != 0
+        {
+            Some(info.RegionSize)
+        } else {
+            None
+        };
+        if !view.is_null() {
+            UnmapViewOfFile(view);
+        }
+        CloseHandle(handle);
+        size
+    }
+}
+
+/// Size of the named shared memory.
+#[cfg(unix)]
+pub(crate) fn memory_size(name: &str) -> Option<usize> {
+    let name = CString::new(name).unwrap();
+    unsafe {
+        let fd = libc::shm_open(name.as_ptr(), libc::O_RDONLY, 0);
+        if fd < 0 {
+            return None;
+        }
+        let mut stat: libc::stat = std::mem::zeroed();
+        let result = libc::fstat(fd, &mut stat);
+        libc::close(fd);
+        (result == 0).then_some(stat.st_size as usize)
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn map_memory<T>(name: &str) -> Option<Shm<T>> {
+    open_memory(name, libc::O_RDWR)
+}
+
+/// Creates (or truncates) a POSIX shared memory object, like the server side of BWAPI would.
+#[cfg(all(test, unix))]
+pub(crate) fn create_memory<T>(name: &str) -> Option<Shm<T>> {
+    open_memory(name, libc::O_RDWR | libc::O_CREAT)
+}
+
+#[cfg(all(test, unix))]
+pub(crate) fn unlink_memory(name: &str) {
+    let name = CString::new(name).unwrap();
+    unsafe {
+        libc::shm_unlink(name.as_ptr());
+    }
+}
+
+#[cfg(unix)]
+fn open_memory<T>(name: &str, flags: libc::c_int) -> Option<Shm<T>> {
+    let memory_size = std::mem::size_of::<T>();
+    let name = CString::new(name).unwrap();
+    unsafe {
+        let fd = libc::shm_open(name.as_ptr(), flags, 0o600);
+        if fd < 0 {
+            // BWAPI Server is most likely not running yet
+            return None;
+        }
+        if flags & libc::O_CREAT != 0 && libc::ftruncate(fd, memory_size as libc::off_t) != 0 {
+            libc::close(fd);
+            return None;
+        }
+        // Accessing a mapping beyond the end of the object raises SIGBUS
+        let mut stat: libc::stat = std::mem::zeroed();
+        if libc::fstat(fd, &mut stat) != 0 || (stat.st_size as usize) < memory_size {
+            libc::close(fd);
+            return None;
+        }
+        let mapped = libc::mmap(
+            core::ptr::null_mut(),
+            memory_size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            fd,
+            0,
+        );
+        // The mapping stays valid after closing the descriptor
+        libc::close(fd);
+        if mapped == libc::MAP_FAILED {
+            return None;
+        }
+        Some(Shm(
+            Mapping::Mmap(memory_size),
+            NonNull::new_unchecked(mapped as *mut T),
+        ))
     }
 }
 
-#[cfg(not(windows))]
+#[cfg(not(any(windows, unix)))]
 pub(crate) fn map_memory<T>(_name: &str) -> Option<Shm<T>> {
     None
 }
+
+#[cfg(not(any(windows, unix)))]
+pub(crate) fn memory_size(_name: &str) -> Option<usize> {
+    None
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_not_map_memory_smaller_than_type() {
+        let name = format!("/rsbwapi_test_small_memory_{}", std::process::id());
+        let _small: Shm<[u8; 16]> = create_memory(&name).unwrap();
+
+        let mapped = map_memory::<[u8; 4096]>(&name);
+        unlink_memory(&name);
+
+        assert!(mapped.is_none());
+    }
+}
@@ -1046,10 +1046,7 @@ mod test {
     use super::*;
 
     use super::*;
-    use crate::{command::Commands, game::*};
-    use inflate::inflate_bytes_zlib;
-    use shm::Shm;
-    use std::cell::RefCell;
+    use crate::game::*;
     use std::fs::*;
     use std::path::Path;
     use std::time::Instant;
@@ -1062,12 +1059,7 @@ mod test {
         {
             let mut target = target.to_path_buf();
             println!("Reading map {:?}", entry.path());
-            let data = read(entry.path()).unwrap();
-            let mut inflated = inflate_bytes_zlib(&data).unwrap();
-            let shm = Shm::from_mut_slice(inflated.as_mut_slice().into());
-            let mut game = Game::new(shm);
-            let commands = RefCell::new(Commands::new());
-            game.match_start();
+            let game = Game::from_frame_buffer_file(entry.path()).unwrap();
             let timer = Instant::now();
             let tm = Map::new(&game);
             println!("{}", timer.elapsed().as_micros());
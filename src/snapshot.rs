@@ -0,0 +1,297 @@
+//! Owned copies of the game state. Unlike `Game`, `Unit` and `Player`, they can be kept beyond
+//! the current frame, sent to other threads and serialized with the `serde` feature.
+use crate::bullet::{Bullet, BulletType};
+use crate::game::Game;
+use crate::player::{Player, PlayerId};
+use crate::unit::{Unit, UnitId};
+use bwapi_wrapper::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UnitSnapshot {
+    pub id: UnitId,
+    pub type_: UnitType,
+    pub player: PlayerId,
+    pub position: Position,
+    pub angle: f64,
+    pub velocity: Vector2D,
+    pub hit_points: i32,
+    pub shields: i32,
+    pub energy: i32,
+    pub resources: i32,
+    pub order: Order,
+    pub target: Option<UnitId>,
+    pub order_target: Option<UnitId>,
+    pub is_completed: bool,
+    pub training_queue: Vec<UnitType>,
+}
+
+impl UnitSnapshot {
+    pub fn new(unit: &Unit) -> Self {
+        Self {
+            id: unit.get_id(),
+            type_: unit.get_type(),
+            player: unit.get_player().id,
+            position: unit.get_position(),
+            angle: unit.get_angle(),
+            velocity: unit.get_velocity(),
+            hit_points: unit.get_hit_points(),
+            shields: unit.get_shields(),
+            energy: unit.get_energy(),
+            resources: unit.get_resources(),
+            order: unit.get_order(),
+            target: unit.get_target().map(|target| target.get_id()),
+            order_target: unit.get_order_target().map(|target| target.get_id()),
+            is_completed: unit.is_completed(),
+            training_queue: unit.get_training_queue(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PlayerSnapshot {
+    pub id: PlayerId,
+    pub name: String,
+    pub race: Race,
+    pub minerals: i32,
+    pub gas: i32,
+    pub gathered_minerals: i32,
+    pub gathered_gas: i32,
+    pub supply_used: i32,
+    pub supply_total: i32,
+    pub is_neutral: bool,
+    pub is_defeated: bool,
+    pub is_victorious: bool,
+}
+
+impl PlayerSnapshot {
+    pub fn new(player: &Player) -> Self {
+        Self {
+            id: player.id,
+            name: player.get_name(),
+            race: player.get_race(),
+            minerals: player.minerals(),
+            gas: player.gas(),
+            gathered_minerals: player.gathered_minerals(),
+            gathered_gas: player.gathered_gas(),
+            supply_used: player.supply_used(),
+            supply_total: player.supply_total(),
+            is_neutral: player.is_neutral(),
+            is_defeated: player.is_defeated(),
+            is_victorious: player.is_victorious(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BulletSnapshot {
+    pub id: usize,
+    pub type_: BulletType,
+    pub player: Option<PlayerId>,
+    pub source: Option<UnitId>,
+    pub target: Option<UnitId>,
+    pub position: Option<Position>,
+    pub target_position: Option<Position>,
+    pub angle: f64,
+}
+
+impl BulletSnapshot {
+    pub fn new(bullet: &Bullet) -> Self {
+        Self {
+            id: bullet.get_id(),
+            type_: bullet.get_type(),
+            player: bullet.get_player().map(|player| player.id),
+            source: bullet.get_source().map(|source| source.get_id()),
+            target: bullet.get_target().map(|target| target.get_id()),
+            position: bullet.get_position(),
+            target_position: bullet.get_target_position(),
+            angle: bullet.get_angle(),
+        }
+    }
+}
+
+/// The state of a frame, see `Game::snapshot`.
+///
+/// The map grids are stored row by row, `walkable` in walk tiles and the others in build tiles.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GameSnapshot {
+    pub frame: i32,
+    pub map_name: String,
+    pub map_hash: String,
+    /// Width in build tiles
+    pub map_width: i32,
+    /// Height in build tiles
+    pub map_height: i32,
+    pub self_: Option<PlayerId>,
+    pub players: Vec<PlayerSnapshot>,
+    /// The units accessible in this frame
+    pub units: Vec<UnitSnapshot>,
+    pub bullets: Vec<BulletSnapshot>,
+    pub walkable: Vec<bool>,
+    pub buildable: Vec<bool>,
+    pub ground_height: Vec<i32>,
+    pub visible: Vec<bool>,
+    pub explored: Vec<bool>,
+    pub creep: Vec<bool>,
+}
+
+fn grid<T>(width: i32, height: i32, cell: impl Fn(i32, i32) -> T) -> Vec<T> {
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| cell(x, y))
+        .collect()
+}
+
+impl GameSnapshot {
+    pub fn new(game: &Game) -> Self {
+        let (width, height) = (game.map_width(), game.map_height());
+        let tile = |x, y| TilePosition { x, y };
+        Self {
+            frame: game.get_frame_count(),
+            map_name: game.map_name(),
+            map_hash: game.map_hash(),
+            map_width: width,
+            map_height: height,
+            self_: game.self_().map(|player| player.id),
+            players: game.players().map(|p| PlayerSnapshot::new(&p)).collect(),
+            units: game.units().iter().map(UnitSnapshot::new).collect(),
+            bullets: game.bullets().map(|b| BulletSnapshot::new(&b)).collect(),
+            walkable: grid(width * 4, height * 4, |x, y| {
+                game.is_walkable(WalkPosition { x, y })
+            }),
+            buildable: grid(width, height, |x, y| game.is_buildable(tile(x, y))),
+            ground_height: grid(width, height, |x, y| game.get_ground_height(tile(x, y))),
+            visible: grid(width, height, |x, y| game.is_visible(tile(x, y))),
+            explored: grid(width, height, |x, y| game.is_explored(tile(x, y))),
+            creep: grid(width, height, |x, y| game.has_creep(tile(x, y))),
+        }
+    }
+
+    pub fn get_unit(&self, id: UnitId) -> Option<&UnitSnapshot> {
+        self.units.iter().find(|unit| unit.id == id)
+    }
+
+    pub fn get_player(&self, id: PlayerId) -> Option<&PlayerSnapshot> {
+        self.players.iter().find(|player| player.id == id)
+    }
+
+    fn tile_index(&self, tp: TilePosition) -> usize {
+        (tp.y * self.map_width + tp.x) as usize
+    }
+
+    pub fn is_walkable<P: Into<WalkPosition>>(&self, wp: P) -> bool {
+        let p = wp.into();
+        self.walkable[(p.y * self.map_width * 4 + p.x) as usize]
+    }
+
+    pub fn is_buildable<P: Into<TilePosition>>(&self, tp: P) -> bool {
+        self.buildable[self.tile_index(tp.into())]
+    }
+
+    pub fn get_ground_height<P: Into<TilePosition>>(&self, tp: P) -> i32 {
+        self.ground_height[self.tile_index(tp.into())]
+    }
+
+    pub fn is_visible<P: Into<TilePosition>>(&self, tp: P) -> bool {
+        self.visible[self.tile_index(tp.into())]
+    }
+
+    pub fn is_explored<P: Into<TilePosition>>(&self, tp: P) -> bool {
+        self.explored[self.tile_index(tp.into())]
+    }
+
+    pub fn has_creep<P: Into<TilePosition>>(&self, tp: P) -> bool {
+        self.creep[self.tile_index(tp.into())]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::aimodule::AiModule;
+    use crate::client::Client;
+    use crate::transport::MemoryTransport;
+    use bwapi_wrapper::*;
+    use std::thread;
+
+    struct Idle;
+
+    impl AiModule for Idle {
+        fn on_frame(&mut self, _game: &Game) {}
+    }
+
+    #[test]
+    fn should_copy_frame_into_snapshot() {
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        let data = client.transport_mut().data_mut();
+        data.playerCount = 1;
+        data.mapWidth = 2;
+        data.mapHeight = 3;
+        data.isBuildable[1][2] = true;
+        data.isWalkable[5][9] = true;
+        let unit = &mut data.units[3];
+        unit.exists = true;
+        unit.type_ = UnitType::Zerg_Hatchery as i32;
+        unit.positionX = 48;
+        unit.trainingQueueCount = 1;
+        unit.trainingQueue[0] = UnitType::Zerg_Drone as i32;
+        data.frameCount = 7;
+        data.eventCount = 1;
+        data.events[0] = BWAPIC_Event {
+            type_: BWAPI_EventType_Enum::UnitShow,
+            v1: 3,
+            v2: 0,
+        };
+        client.update(&mut Idle).unwrap();
+
+        let snapshot = client.get_game().snapshot();
+        drop(client);
+
+        let snapshot = thread::spawn(move || snapshot).join().unwrap();
+        assert_eq!(snapshot.frame, 7);
+        assert_eq!(snapshot.players.len(), 1);
+        let hatchery = snapshot.get_unit(3).unwrap();
+        assert_eq!(hatchery.type_, UnitType::Zerg_Hatchery);
+        assert_eq!(hatchery.position, Position::new(48, 0));
+        assert_eq!(hatchery.training_queue, [UnitType::Zerg_Drone]);
+        assert!(snapshot.is_buildable(TilePosition { x: 1, y: 2 }));
+        assert!(!snapshot.is_buildable(TilePosition { x: 0, y: 2 }));
+        assert!(snapshot.is_walkable(WalkPosition { x: 5, y: 9 }));
+        assert_eq!(snapshot.walkable.len(), 8 * 12);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn should_round_trip_snapshots_with_enum_names() {
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        let data = client.transport_mut().data_mut();
+        data.playerCount = 1;
+        let unit = &mut data.units[3];
+        unit.exists = true;
+        unit.type_ = UnitType::Zerg_Hatchery as i32;
+        unit.order = Order::Train as i32;
+        unit.trainingQueueCount = 1;
+        unit.trainingQueue[0] = UnitType::Zerg_Drone as i32;
+        data.eventCount = 1;
+        data.events[0] = BWAPIC_Event {
+            type_: BWAPI_EventType_Enum::UnitShow,
+            v1: 3,
+            v2: 0,
+        };
+        client.update(&mut Idle).unwrap();
+        let snapshot = client.get_game().snapshot();
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+
+        assert!(json.contains(r#""type_":"Zerg_Hatchery""#));
+        assert!(json.contains(r#""order":"Train""#));
+        assert!(json.contains(r#""training_queue":["Zerg_Drone"]"#));
+        let restored: GameSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, snapshot);
+    }
+}
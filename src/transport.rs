@@ -0,0 +1,322 @@
+use bwapi_wrapper::*;
+
+#[cfg(windows)]
+use std::fs::File;
+#[cfg(windows)]
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::ops::Deref;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::client::{ClientError, ConnectOptions, Instance};
+use crate::shm::{self, Shm};
+
+/// The `BWAPI_GameData` of a connection. Shared by the transport, which updates it, and every
+/// `Game` reading from it; the memory is released once the last of them is dropped.
+#[derive(Clone)]
+pub struct SharedGameData(Rc<Shm<BWAPI_GameData>>);
+
+impl SharedGameData {
+    /// Zeroed game data on the heap, for transports that don't receive frames via shared memory.
+    pub fn zeroed() -> Self {
+        Self::new(Shm::zeroed())
+    }
+
+    pub(crate) fn new(shm: Shm<BWAPI_GameData>) -> Self {
+        Self(Rc::new(shm))
+    }
+
+    /// Pointer to the game data, valid for as long as this `SharedGameData` or any clone of it
+    /// lives. See `Transport` for when it may be written to.
+    pub fn as_ptr(&self) -> *mut BWAPI_GameData {
+        self.0.as_ptr() as *mut _
+    }
+}
+
+impl Deref for SharedGameData {
+    type Target = BWAPI_GameData;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Connection to a BWAPI server.
+///
+/// A frame is exchanged by first calling `signal_done`, which tells the server that the client
+/// is done with the current frame, and then `wait_for_frame`, which blocks until the server has
+/// written the next frame into the game data.
+///
+/// # Safety
+///
+/// `Game`, `Unit` and `Player` read the game data returned by `connect` through shared
+/// references. Implementations must only write to it in `connect`, `wait_for_frame` and in
+/// methods taking `&mut self` (like `MemoryTransport::data_mut`), never concurrently from
+/// another thread. Every write must leave a valid `BWAPI_GameData` behind: enum fields like the
+/// event types have to hold a known discriminant and every `bool` has to be 0 or 1.
+pub unsafe trait Transport {
+    /// Performs the initial handshake and hands over the game data. Called exactly once, before
+    /// any other method.
+    fn connect(&mut self) -> Result<SharedGameData, ClientError>;
+
+    /// Blocks until the server has published the next frame.
+    fn wait_for_frame(&mut self) -> Result<(), ClientError>;
+
+    /// Tells the server that all commands for the current frame were written.
+    fn signal_done(&mut self) -> Result<(), ClientError>;
+}
+
+#[cfg(windows)]
+type Pipe = File;
+#[cfg(unix)]
+type Pipe = UnixStream;
+
+#[cfg(windows)]
+const GAME_TABLE_NAME: &str = "Local\\bwapi_shared_memory_game_list";
+#[cfg(unix)]
+const GAME_TABLE_NAME: &str = "/bwapi_shared_memory_game_list";
+
+#[cfg(windows)]
+pub(crate) fn game_data_name(pid: u32) -> String {
+    format!("Local\\bwapi_shared_memory_{}", pid)
+}
+
+#[cfg(unix)]
+pub(crate) fn game_data_name(pid: u32) -> String {
+    format!("/bwapi_shared_memory_{}", pid)
+}
+
+#[cfg(windows)]
+pub(crate) fn pipe_name(pid: u32) -> String {
+    format!("\\\\.\\pipe\\bwapi_pipe_{}", pid)
+}
+
+#[cfg(unix)]
+pub(crate) fn pipe_name(pid: u32) -> String {
+    format!("/tmp/bwapi_socket_{}", pid)
+}
+
+#[cfg(windows)]
+fn open_pipe(name: &str) -> std::io::Result<Pipe> {
+    OpenOptions::new().read(true).write(true).open(name)
+}
+
+#[cfg(unix)]
+fn open_pipe(name: &str) -> std::io::Result<Pipe> {
+    UnixStream::connect(name)
+}
+
+/// Pipes opened as files don't support timeouts, so the handshake may block after the deadline.
+#[cfg(windows)]
+fn set_read_timeout(_pipe: &Pipe, _timeout: Option<Duration>) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_read_timeout(pipe: &Pipe, timeout: Option<Duration>) -> std::io::Result<()> {
+    pipe.set_read_timeout(timeout)
+}
+
+/// Windows reports the size of shared memory in whole pages
+#[cfg(windows)]
+const PAGE_SIZE: usize = 4096;
+
+/// Makes sure the game data shared memory `name` has the size of the `BWAPI_GameData` the
+/// bindings were generated for.
+fn check_layout(name: &str, pid: u32) -> Result<(), ClientError> {
+    let actual = shm::memory_size(name).ok_or(ClientError::GameDataNotMapped(pid))?;
+    let expected = std::mem::size_of::<BWAPI_GameData>();
+    #[cfg(windows)]
+    let matches = actual == expected.next_multiple_of(PAGE_SIZE);
+    #[cfg(not(windows))]
+    let matches = actual == expected;
+    if matches {
+        Ok(())
+    } else {
+        Err(ClientError::LayoutMismatch { expected, actual })
+    }
+}
+
+/// The transport used by BWAPI itself: A named pipe (Unix socket with OpenBW) for signaling and
+/// shared memory for the game data.
+pub struct PipeTransport {
+    options: ConnectOptions,
+    game_table_name: String,
+    pipe: Option<Pipe>,
+}
+
+impl Default for PipeTransport {
+    fn default() -> Self {
+        Self::new(ConnectOptions::default())
+    }
+}
+
+impl PipeTransport {
+    pub fn new(options: ConnectOptions) -> Self {
+        Self::with_game_table(options, GAME_TABLE_NAME)
+    }
+
+    pub(crate) fn with_game_table(options: ConnectOptions, game_table_name: &str) -> Self {
+        Self {
+            options,
+            game_table_name: game_table_name.to_string(),
+            pipe: None,
+        }
+    }
+
+    fn pipe(&mut self) -> &mut Pipe {
+        self.pipe.as_mut().expect("Transport is not connected")
+    }
+
+    /// Opens the pipe of server `pid` and waits for its first frame, at most until `deadline`.
+    fn handshake(&mut self, pid: u32, deadline: Option<Instant>) -> Result<(), ClientError> {
+        let pipe = open_pipe(&pipe_name(pid))?;
+        let timeout = deadline
+            .map(|deadline| {
+                deadline
+                    .checked_duration_since(Instant::now())
+                    .filter(|remaining| !remaining.is_zero())
+                    .ok_or(ClientError::Timeout)
+            })
+            .transpose()?;
+        set_read_timeout(&pipe, timeout)?;
+        self.pipe = Some(pipe);
+        self.wait_for_frame()?;
+        set_read_timeout(self.pipe(), None)?;
+        Ok(())
+    }
+}
+
+// The server writes the game data only between `signal_done` and the end of `wait_for_frame`.
+unsafe impl Transport for PipeTransport {
+    fn connect(&mut self) -> Result<SharedGameData, ClientError> {
+        let deadline = self.options.timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if let Some(game_table) = shm::map_memory::<BWAPI_GameTable>(&self.game_table_name) {
+                let pids: Vec<u32> = game_table
+                    .get()
+                    .gameInstances
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, game_instance)| {
+                        game_instance.serverProcessID != 0 && !game_instance.isConnected
+                    })
+                    .filter(|(i, game_instance)| match self.options.instance {
+                        Instance::Any => true,
+                        Instance::ServerPid(pid) => game_instance.serverProcessID == pid,
+                        Instance::InstanceNumber(number) => *i as i32 == number,
+                    })
+                    .map(|(_, game_instance)| game_instance.serverProcessID)
+                    .collect();
+                for pid in pids {
+                    println!("Connecting to {}", pid);
+                    // Entries of crashed servers stay in the game table, try the next one
+                    if let Err(err) = self.handshake(pid, deadline) {
+                        println!("Could not connect to {}: {}", pid, err);
+                        self.pipe = None;
+                        continue;
+                    }
+                    println!("Connected to {}", pid);
+                    let name = game_data_name(pid);
+                    // Before mapping, accessing a smaller mapping would crash
+                    check_layout(&name, pid)?;
+                    let game_data: Shm<BWAPI_GameData> =
+                        shm::map_memory(&name).ok_or(ClientError::GameDataNotMapped(pid))?;
+                    let server = game_data.get().client_version;
+                    if server != CLIENT_VERSION {
+                        return Err(ClientError::VersionMismatch {
+                            server,
+                            client: CLIENT_VERSION,
+                        });
+                    }
+                    return Ok(SharedGameData::new(game_data));
+                }
+            } else {
+                println!("Game table mapping not found.");
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(ClientError::Timeout);
+            }
+            thread::sleep(self.options.retry_interval);
+        }
+    }
+
+    fn wait_for_frame(&mut self) -> Result<(), ClientError> {
+        let mut buf: [u8; 1] = [0];
+        loop {
+            self.pipe().read_exact(&mut buf)?;
+            if buf[0] == 2 {
+                return Ok(());
+            }
+        }
+    }
+
+    fn signal_done(&mut self) -> Result<(), ClientError> {
+        self.pipe().write_all(&[1])?;
+        Ok(())
+    }
+}
+
+/// Game data living on the heap, without any server. Frames are produced by writing into
+/// `data_mut` between calls to `Client::update`. Useful for testing bots and `Game` without
+/// StarCraft.
+pub struct MemoryTransport {
+    data: SharedGameData,
+}
+
+impl Default for MemoryTransport {
+    fn default() -> Self {
+        Self {
+            data: SharedGameData::zeroed(),
+        }
+    }
+}
+
+impl MemoryTransport {
+    pub fn data(&self) -> &BWAPI_GameData {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut BWAPI_GameData {
+        unsafe { &mut *self.data.as_ptr() }
+    }
+}
+
+// Only `data_mut` writes, and only values of the right types.
+unsafe impl Transport for MemoryTransport {
+    fn connect(&mut self) -> Result<SharedGameData, ClientError> {
+        Ok(self.data.clone())
+    }
+
+    fn wait_for_frame(&mut self) -> Result<(), ClientError> {
+        Ok(())
+    }
+
+    fn signal_done(&mut self) -> Result<(), ClientError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn should_reject_game_data_of_different_size() {
+        let name = format!("/rsbwapi_test_small_game_data_{}", std::process::id());
+        let _small: Shm<[u8; 16]> = shm::create_memory(&name).unwrap();
+
+        let result = check_layout(&name, 1);
+        shm::unlink_memory(&name);
+
+        assert!(matches!(
+            result,
+            Err(ClientError::LayoutMismatch { actual: 16, .. })
+        ));
+    }
+}
@@ -72,6 +72,7 @@ pub(crate) fn c_str_to_str(i: &[c_char]) -> String {
 }
 
 #[derive(Default, Debug, Clone, Copy, Eq, PartialEq, Add, Sub, AddAssign, SubAssign)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rectangle<P> {
     pub tl: P,
     pub br: P,
@@ -1,6 +1,7 @@
 use crate::player::Player;
 use crate::predicate::{IntoPredicate, Predicate};
 use crate::projected::Projected;
+use crate::unit_map::UnitLifecycle;
 
 use crate::*;
 use bwapi_wrapper::*;
@@ -17,6 +18,18 @@ pub(crate) struct UnitInfo {
     pub last_command_frame: i32,
 }
 
+impl Default for UnitInfo {
+    fn default() -> Self {
+        Self {
+            initial_hit_points: 0,
+            initial_resources: 0,
+            initial_position: Position::new(-1, -1),
+            initial_type: UnitType::None,
+            last_command_frame: -10,
+        }
+    }
+}
+
 impl UnitInfo {
     pub(crate) fn new(data: &BWAPI_UnitData) -> Self {
         Self {
@@ -35,9 +48,47 @@ impl UnitInfo {
 #[derive(Clone)]
 pub struct Unit {
     id: UnitId,
+    /// Generation of the id when this handle was created, see `is_stale`
+    generation: u32,
     pub(crate) inner: Projected<Game, BWAPI_UnitData>,
 }
 
+/// A handle to a unit that does not resolve to a different unit once the id is reused.
+#[derive(Clone)]
+pub struct WeakUnit {
+    id: UnitId,
+    generation: u32,
+    game: Game,
+}
+
+impl WeakUnit {
+    pub fn get_id(&self) -> UnitId {
+        self.id
+    }
+
+    /// The unit, unless it was destroyed or a new game started. Hidden units are still returned.
+    pub fn upgrade(&self) -> Option<Unit> {
+        self.game
+            .get_unit(self.id)
+            .filter(|unit| unit.generation == self.generation)
+    }
+}
+
+impl fmt::Debug for WeakUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WeakUnit")
+            .field("id", &self.id)
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+impl PartialEq for WeakUnit {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.generation == other.generation
+    }
+}
+
 impl From<Unit> for UnitId {
     fn from(unit: Unit) -> Self {
         unit.id
@@ -59,8 +110,10 @@ impl Unit {
         UnitType::new(self.inner.type_)
     }
     pub(crate) fn new(id: UnitId, game: Game, data: *const BWAPI_UnitData) -> Self {
+        let generation = game.inner.lifecycles.borrow()[id].generation;
         Unit {
             id,
+            generation,
             inner: unsafe { Projected::new(game, data) },
         }
     }
@@ -73,6 +126,29 @@ impl Unit {
         (*self.inner.game().inner.unit_infos.borrow_mut())[self.id] = unit_info;
     }
 
+    pub(crate) fn lifecycle(&self) -> UnitLifecycle {
+        self.inner.game().inner.lifecycles.borrow()[self.id]
+    }
+
+    pub(crate) fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Whether this unit was destroyed or is from a previous game. The id might now belong to a
+    /// different unit, whose data would be returned by all other accessors.
+    pub fn is_stale(&self) -> bool {
+        self.lifecycle().generation != self.generation
+    }
+
+    /// A handle to keep between frames.
+    pub fn downgrade(&self) -> WeakUnit {
+        WeakUnit {
+            id: self.id,
+            generation: self.generation,
+            game: self.inner.game().clone(),
+        }
+    }
+
     pub(crate) fn get_buttonset(&self) -> i32 {
         self.inner.buttonset
     }
@@ -223,8 +299,8 @@ impl Unit {
         }
         self.inner
             .game()
-            .get_all_units()
-            .iter()
+            .units()
+            .of_type(UnitType::Protoss_Interceptor)
             .filter(|u| u.get_carrier().as_ref() == Some(self))
             .cloned()
             .collect()
@@ -244,8 +320,8 @@ impl Unit {
         }
         self.inner
             .game()
-            .get_all_units()
-            .iter()
+            .units()
+            .of_type(UnitType::Zerg_Larva)
             .filter(|u| u.get_hatchery().as_ref() == Some(self))
             .cloned()
             .collect()
@@ -264,7 +340,7 @@ impl Unit {
     pub fn get_loaded_units(&self) -> Vec<Unit> {
         self.inner
             .game()
-            .get_all_units()
+            .units()
             .iter()
             .filter(|u| {
                 if let Some(transport) = u.get_transport() {
@@ -293,6 +369,45 @@ impl Unit {
         Order::new(self.inner.order)
     }
 
+    /// Hit points and shields lost since the previous frame, 0 if the unit was not accessible.
+    pub fn get_damage_taken(&self) -> i32 {
+        self.inner
+            .game()
+            .get_previous_unit_state(self)
+            .map_or(0, |previous| {
+                (previous.hit_points + previous.shields
+                    - self.get_hit_points()
+                    - self.get_shields())
+                .max(0)
+            })
+    }
+
+    /// Whether the order differs from the previous frame.
+    pub fn order_changed(&self) -> bool {
+        self.inner
+            .game()
+            .get_previous_unit_state(self)
+            .is_some_and(|previous| previous.order != self.get_order())
+    }
+
+    /// Whether a unit was added to the training queue since the previous frame.
+    pub fn started_training(&self) -> bool {
+        self.inner
+            .game()
+            .get_previous_unit_state(self)
+            .is_some_and(|previous| previous.training_queue_count < self.inner.trainingQueueCount)
+    }
+
+    /// Distance moved since the previous frame.
+    pub fn get_position_delta(&self) -> Position {
+        self.inner
+            .game()
+            .get_previous_unit_state(self)
+            .map_or(Position::new(0, 0), |previous| {
+                self.get_position() - previous.position
+            })
+    }
+
     pub fn get_order_target(&self) -> Option<Unit> {
         self.inner.game().get_unit(self.inner.orderTarget as usize)
     }
@@ -856,7 +971,7 @@ impl Unit {
     }
 
     pub fn exists(&self) -> bool {
-        self.inner.exists
+        self.inner.exists && !self.is_stale()
     }
 
     pub fn has_path<P: UnitOrPosition>(&self, target: P) -> bool {
@@ -1344,6 +1459,52 @@ impl<I: Into<Position> + Copy> UnitOrPosition for I {
 
 impl PartialEq for Unit {
     fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
+        self.id == other.id && self.generation == other.generation
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::aimodule::AiModule;
+    use crate::client::Client;
+    use crate::transport::MemoryTransport;
+
+    struct Idle;
+
+    impl AiModule for Idle {
+        fn on_frame(&mut self, _game: &Game) {}
+    }
+
+    fn play_event(client: &mut Client<MemoryTransport>, type_: BWAPI_EventType_Enum) {
+        let data = client.transport_mut().data_mut();
+        data.frameCount += 1;
+        data.eventCount = 1;
+        data.events[0] = BWAPIC_Event {
+            type_,
+            v1: 3,
+            v2: 0,
+        };
+        client.update(&mut Idle).unwrap();
+    }
+
+    #[test]
+    fn should_detect_stale_unit_after_id_was_reused() {
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        client.transport_mut().data_mut().units[3].exists = true;
+        play_event(&mut client, BWAPI_EventType_Enum::UnitCreate);
+        let unit = client.get_game().get_unit(3).unwrap();
+        let weak = unit.downgrade();
+        assert_eq!(weak.upgrade(), Some(unit.clone()));
+
+        play_event(&mut client, BWAPI_EventType_Enum::UnitDestroy);
+        play_event(&mut client, BWAPI_EventType_Enum::UnitCreate);
+
+        assert!(unit.is_stale());
+        assert!(!unit.exists());
+        assert_eq!(weak.upgrade(), None);
+        let new_unit = client.get_game().get_unit(3).unwrap();
+        assert!(!new_unit.is_stale());
+        assert_ne!(unit, new_unit);
     }
 }
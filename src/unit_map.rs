@@ -0,0 +1,288 @@
+use crate::game::Game;
+use crate::unit::{Unit, UnitId};
+use std::cell::{Ref, RefCell, RefMut};
+use std::rc::Rc;
+
+/// Tracks how often the unit with an id was replaced, morphed or changed owner.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct UnitLifecycle {
+    /// Changes when the id is freed by `UnitDestroy` or a new game starts
+    pub generation: u32,
+    pub morphs: u32,
+    pub renegades: u32,
+}
+
+struct Slot<T> {
+    lifecycle: UnitLifecycle,
+    value: T,
+}
+
+type Slots<T> = RefCell<Vec<Option<Slot<T>>>>;
+
+/// The values of a `UnitMap`, as seen by the `Game` it was created with.
+pub(crate) trait UnitSlots {
+    /// Drops the values of the ids starting at `first`, whose generation differs from the one in
+    /// `lifecycles`.
+    fn drop_stale(&self, first: UnitId, lifecycles: &[UnitLifecycle]);
+}
+
+impl<T> UnitSlots for Slots<T> {
+    fn drop_stale(&self, first: UnitId, lifecycles: &[UnitLifecycle]) {
+        // Borrowed by the bot, the value is replaced once the id is reused instead
+        let Ok(mut slots) = self.try_borrow_mut() else {
+            return;
+        };
+        let stale: Vec<_> = slots
+            .iter_mut()
+            .skip(first)
+            .zip(lifecycles)
+            .filter(|(slot, lifecycle)| {
+                slot.as_ref()
+                    .is_some_and(|slot| slot.lifecycle.generation != lifecycle.generation)
+            })
+            .filter_map(|(slot, _)| slot.take())
+            .collect();
+        // Values may use the map when dropped
+        drop(slots);
+        drop(stale);
+    }
+}
+
+fn slot<'a, T>(slots: &'a [Option<Slot<T>>], unit: &Unit) -> Option<&'a Slot<T>> {
+    if unit.is_stale() {
+        return None;
+    }
+    let generation = unit.generation();
+    slots
+        .get(unit.get_id())?
+        .as_ref()
+        .filter(|slot| slot.lifecycle.generation == generation)
+}
+
+fn slot_mut<'a, T>(slots: &'a mut [Option<Slot<T>>], unit: &Unit) -> Option<&'a mut Slot<T>> {
+    if unit.is_stale() {
+        return None;
+    }
+    let generation = unit.generation();
+    slots
+        .get_mut(unit.get_id())?
+        .as_mut()
+        .filter(|slot| slot.lifecycle.generation == generation)
+}
+
+/// Stores bot data per unit.
+///
+/// Ids are reused by BWAPI once a unit is destroyed. The `Game` the map was created with drops
+/// the value of a unit right after `on_unit_destroy`, and all values when a new game starts.
+/// Entries survive `UnitMorph` and `UnitRenegade`, use `has_morphed` and `has_changed_owner` to
+/// check if that happened since the value was inserted.
+///
+/// Values are borrowed like the contents of a `RefCell`. A value that is still borrowed when its
+/// unit is destroyed is never returned again, and dropped once its id is reused.
+pub struct UnitMap<T> {
+    slots: Rc<Slots<T>>,
+}
+
+impl<T: 'static> UnitMap<T> {
+    pub fn new(game: &Game) -> Self {
+        let slots = Rc::new(RefCell::new(vec![]));
+        game.register_unit_map(Rc::downgrade(&slots) as _);
+        Self { slots }
+    }
+}
+
+impl<T> UnitMap<T> {
+    pub fn get(&self, unit: &Unit) -> Option<Ref<'_, T>> {
+        Ref::filter_map(self.slots.borrow(), |slots| {
+            slot(slots, unit).map(|slot| &slot.value)
+        })
+        .ok()
+    }
+
+    pub fn get_mut(&mut self, unit: &Unit) -> Option<RefMut<'_, T>> {
+        RefMut::filter_map(self.slots.borrow_mut(), |slots| {
+            slot_mut(slots, unit).map(|slot| &mut slot.value)
+        })
+        .ok()
+    }
+
+    pub fn contains(&self, unit: &Unit) -> bool {
+        slot(&self.slots.borrow(), unit).is_some()
+    }
+
+    /// Returns the previous value of `unit`, if it was inserted for the same unit.
+    pub fn insert(&mut self, unit: &Unit, value: T) -> Option<T> {
+        let id = unit.get_id();
+        let mut slots = self.slots.borrow_mut();
+        if id >= slots.len() {
+            slots.resize_with(id + 1, || None);
+        }
+        let lifecycle = UnitLifecycle {
+            generation: unit.generation(),
+            ..unit.lifecycle()
+        };
+        slots[id]
+            .replace(Slot { lifecycle, value })
+            .filter(|slot| slot.lifecycle.generation == lifecycle.generation)
+            .map(|slot| slot.value)
+    }
+
+    pub fn get_or_insert_with(&mut self, unit: &Unit, f: impl FnOnce() -> T) -> RefMut<'_, T> {
+        if !self.contains(unit) {
+            self.insert(unit, f());
+        }
+        RefMut::map(self.slots.borrow_mut(), |slots| {
+            let slot = slots[unit.get_id()].as_mut();
+            &mut slot.expect("Value was just inserted").value
+        })
+    }
+
+    pub fn remove(&mut self, unit: &Unit) -> Option<T> {
+        let mut slots = self.slots.borrow_mut();
+        slot(&slots, unit)?;
+        slots[unit.get_id()].take().map(|slot| slot.value)
+    }
+
+    /// Whether `unit` morphed since its value was inserted.
+    pub fn has_morphed(&self, unit: &Unit) -> bool {
+        slot(&self.slots.borrow(), unit)
+            .is_some_and(|slot| slot.lifecycle.morphs != unit.lifecycle().morphs)
+    }
+
+    /// Whether `unit` changed its owner since its value was inserted.
+    pub fn has_changed_owner(&self, unit: &Unit) -> bool {
+        slot(&self.slots.borrow(), unit)
+            .is_some_and(|slot| slot.lifecycle.renegades != unit.lifecycle().renegades)
+    }
+
+    /// Calls `f` for all accessible units that have a value.
+    pub fn for_each_mut(&mut self, game: &Game, mut f: impl FnMut(Unit, &mut T)) {
+        for (id, slot) in self.slots.borrow_mut().iter_mut().enumerate() {
+            if let Some(unit) = game.get_unit(id as UnitId)
+                && let Some(slot) = slot_mut(std::slice::from_mut(slot), &unit)
+            {
+                f(unit, &mut slot.value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::aimodule::AiModule;
+    use crate::client::Client;
+    use crate::transport::MemoryTransport;
+    use bwapi_wrapper::*;
+
+    struct Tagging {
+        tags: UnitMap<&'static str>,
+        morphed: bool,
+    }
+
+    impl Tagging {
+        fn new(game: &Game) -> Self {
+            Self {
+                tags: UnitMap::new(game),
+                morphed: false,
+            }
+        }
+    }
+
+    impl AiModule for Tagging {
+        fn on_frame(&mut self, _game: &Game) {}
+
+        fn on_unit_create(&mut self, _game: &Game, unit: Unit) {
+            assert!(self.tags.get(&unit).is_none());
+            self.tags.insert(&unit, "larva");
+        }
+
+        fn on_unit_morph(&mut self, _game: &Game, unit: Unit) {
+            self.morphed = self.tags.has_morphed(&unit);
+        }
+
+        fn on_unit_destroy(&mut self, _game: &Game, unit: Unit) {
+            assert_eq!(self.tags.get(&unit).as_deref(), Some(&"larva"));
+        }
+    }
+
+    fn play_frame(
+        client: &mut Client<MemoryTransport>,
+        module: &mut impl AiModule,
+        events: &[(BWAPI_EventType_Enum, i32)],
+    ) {
+        let data = client.transport_mut().data_mut();
+        data.frameCount += 1;
+        data.eventCount = events.len() as i32;
+        for (target, &(type_, v1)) in data.events.iter_mut().zip(events) {
+            *target = BWAPIC_Event { type_, v1, v2: 0 };
+        }
+        client.update(module).unwrap();
+    }
+
+    #[test]
+    fn should_keep_values_on_morph_and_drop_them_on_destroy() {
+        use BWAPI_EventType_Enum::*;
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        client.transport_mut().data_mut().units[3].exists = true;
+        let mut module = Tagging::new(client.get_game());
+
+        play_frame(&mut client, &mut module, &[(UnitCreate, 3), (UnitMorph, 3)]);
+        let unit = client.get_game().get_unit(3).unwrap();
+        assert!(module.tags.contains(&unit));
+        assert!(module.morphed);
+        assert!(!module.tags.has_changed_owner(&unit));
+
+        play_frame(&mut client, &mut module, &[(UnitDestroy, 3)]);
+        assert!(module.tags.get(&unit).is_none());
+        let mut count = 0;
+        module
+            .tags
+            .for_each_mut(client.get_game(), |_, _| count += 1);
+        assert_eq!(count, 0);
+
+        // Same id, different unit, which got its own value in `on_unit_create`
+        play_frame(&mut client, &mut module, &[(UnitCreate, 3)]);
+        assert!(module.tags.get(&unit).is_none());
+        let unit = client.get_game().get_unit(3).unwrap();
+        *module.tags.get_mut(&unit).unwrap() = "drone";
+        assert_eq!(module.tags.insert(&unit, "hatchery"), Some("drone"));
+        assert_eq!(module.tags.remove(&unit), Some("hatchery"));
+    }
+
+    #[derive(Default)]
+    struct Tracked(Option<UnitMap<Rc<()>>>, Rc<()>);
+
+    impl AiModule for Tracked {
+        fn on_frame(&mut self, _game: &Game) {}
+
+        fn on_unit_create(&mut self, game: &Game, unit: Unit) {
+            let value = self.1.clone();
+            self.0
+                .get_or_insert_with(|| UnitMap::new(game))
+                .insert(&unit, value);
+        }
+    }
+
+    #[test]
+    fn should_drop_value_as_soon_as_unit_is_destroyed() {
+        use BWAPI_EventType_Enum::*;
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        client.transport_mut().data_mut().units[3].exists = true;
+        client.transport_mut().data_mut().units[4].exists = true;
+        let mut module = Tracked::default();
+
+        play_frame(
+            &mut client,
+            &mut module,
+            &[(UnitCreate, 3), (UnitCreate, 4)],
+        );
+        assert_eq!(Rc::strong_count(&module.1), 3);
+
+        play_frame(&mut client, &mut module, &[(UnitDestroy, 3)]);
+        assert_eq!(Rc::strong_count(&module.1), 2);
+
+        play_frame(&mut client, &mut module, &[(MatchStart, 0)]);
+        assert_eq!(Rc::strong_count(&module.1), 1);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::game::Game;
+use crate::player::PlayerId;
+use crate::predicate::{IntoPredicate, Predicate};
+use crate::unit::{Unit, UnitId};
+use bwapi_wrapper::prelude::*;
+use std::collections::BTreeMap;
+
+/// The last known state of an enemy unit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RememberedUnit {
+    pub id: UnitId,
+    pub type_: UnitType,
+    pub player: PlayerId,
+    pub position: Position,
+    pub hit_points: i32,
+    pub shields: i32,
+    pub is_completed: bool,
+    /// Frame in which the unit was visible the last time
+    pub last_seen: i32,
+    /// Whether the unit is visible in the current frame
+    pub visible: bool,
+    /// Visible units and hidden buildings, as long as their position was not seen empty
+    pub probably_there: bool,
+}
+
+impl RememberedUnit {
+    fn new(unit: &Unit, frame: i32) -> Self {
+        Self {
+            id: unit.get_id(),
+            type_: unit.get_type(),
+            player: unit.get_player().id,
+            position: unit.get_position(),
+            hit_points: unit.get_hit_points(),
+            shields: unit.get_shields(),
+            is_completed: unit.is_completed(),
+            last_seen: frame,
+            visible: true,
+            probably_there: true,
+        }
+    }
+
+    pub fn get_tile_position(&self) -> TilePosition {
+        (self.position
+            - Position {
+                x: self.type_.tile_width(),
+                y: self.type_.tile_height(),
+            } * 32
+                / 2)
+        .to_tile_position()
+    }
+
+    pub fn get_left(&self) -> i32 {
+        self.position.x - self.type_.dimension_left()
+    }
+
+    pub fn get_top(&self) -> i32 {
+        self.position.y - self.type_.dimension_up()
+    }
+
+    pub fn get_right(&self) -> i32 {
+        self.position.x + self.type_.dimension_right()
+    }
+
+    pub fn get_bottom(&self) -> i32 {
+        self.position.y + self.type_.dimension_down()
+    }
+}
+
+/// Keeps the last known state of all enemy units, including the ones that are no longer visible.
+///
+/// Entries are only removed if the unit was destroyed or no longer belongs to an enemy. It is
+/// maintained by `Game` and available with `Game::get_unit_memory`.
+#[derive(Clone, Debug, Default)]
+pub struct UnitMemory {
+    units: BTreeMap<UnitId, RememberedUnit>,
+}
+
+impl UnitMemory {
+    pub fn get(&self, id: UnitId) -> Option<&RememberedUnit> {
+        self.units.get(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RememberedUnit> {
+        self.units.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.units.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+
+    pub fn get_units<P: IntoPredicate<RememberedUnit>>(&self, pred: P) -> Vec<&RememberedUnit> {
+        let pred = pred.into_predicate();
+        self.iter().filter(|u| pred.test(u)).collect()
+    }
+
+    pub fn get_units_in_rectangle<
+        A: Into<Position>,
+        B: Into<Position>,
+        P: IntoPredicate<RememberedUnit>,
+    >(
+        &self,
+        lt: A,
+        rb: B,
+        pred: P,
+    ) -> Vec<&RememberedUnit> {
+        let lt = lt.into();
+        let rb = rb.into();
+        let pred = pred.into_predicate();
+        self.iter()
+            .filter(|u| {
+                u.get_left() <= rb.x
+                    && u.get_right() >= lt.x
+                    && u.get_top() <= rb.y
+                    && u.get_bottom() >= lt.y
+                    && pred.test(u)
+            })
+            .collect()
+    }
+
+    pub fn get_units_in_radius<P: Into<Position>, Pred: IntoPredicate<RememberedUnit>>(
+        &self,
+        position: P,
+        radius: i32,
+        pred: Pred,
+    ) -> Vec<&RememberedUnit> {
+        let center = position.into();
+        let radius_sq = (radius * radius) as u32;
+        let pred = pred.into_predicate();
+        self.iter()
+            .filter(|u| center.distance_squared(u.position) <= radius_sq && pred.test(u))
+            .collect()
+    }
+
+    pub fn get_closest_unit<P: Into<Position>, Pred: IntoPredicate<RememberedUnit>>(
+        &self,
+        center: P,
+        pred: Pred,
+        radius: impl Into<Option<i32>>,
+    ) -> Option<&RememberedUnit> {
+        let center = center.into();
+        let radius = radius.into().unwrap_or(32000);
+        self.get_units_in_radius(center, radius, pred)
+            .into_iter()
+            .min_by_key(|u| center.distance_squared(u.position))
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.units.clear();
+    }
+
+    pub(crate) fn forget(&mut self, id: UnitId) {
+        self.units.remove(&id);
+    }
+
+    /// Records the state of the currently visible enemy units.
+    pub(crate) fn update(&mut self, game: &Game, visible_units: &[Unit]) {
+        let Some(self_) = game.self_() else {
+            return;
+        };
+        let frame = game.get_frame_count();
+        for remembered in self.units.values_mut() {
+            remembered.visible = false;
+        }
+        for unit in visible_units {
+            if unit.get_player().is_enemy(&self_) {
+                self.units
+                    .insert(unit.get_id(), RememberedUnit::new(unit, frame));
+            } else {
+                // Mind controlled or otherwise changed sides
+                self.forget(unit.get_id());
+            }
+        }
+        for remembered in self.units.values_mut() {
+            remembered.probably_there = remembered.visible
+                || remembered.type_.is_building()
+                    && remembered.probably_there
+                    && !game.is_visible(remembered.position.to_tile_position());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::aimodule::AiModule;
+    use crate::client::Client;
+    use crate::transport::MemoryTransport;
+    use bwapi_wrapper::*;
+
+    struct Idle;
+
+    impl AiModule for Idle {
+        fn on_frame(&mut self, _game: &Game) {}
+    }
+
+    fn play_frame(client: &mut Client<MemoryTransport>, events: &[(BWAPI_EventType_Enum, i32)]) {
+        let data = client.transport_mut().data_mut();
+        data.frameCount += 1;
+        data.eventCount = events.len() as i32;
+        for (target, &(type_, v1)) in data.events.iter_mut().zip(events) {
+            *target = BWAPIC_Event { type_, v1, v2: 0 };
+        }
+        client.update(&mut Idle).unwrap();
+    }
+
+    #[test]
+    fn should_remember_hidden_buildings_until_seen_empty_or_destroyed() {
+        use BWAPI_EventType_Enum::*;
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        let data = client.transport_mut().data_mut();
+        data.playerCount = 2;
+        data.self_ = 0;
+        data.players[1].isEnemy[0] = true;
+        let unit = &mut data.units[3];
+        unit.exists = true;
+        unit.player = 1;
+        unit.type_ = UnitType::Terran_Barracks as i32;
+        unit.positionX = 320;
+        unit.positionY = 160;
+        unit.hitPoints = 1000;
+        play_frame(&mut client, &[(UnitShow, 3), (MatchFrame, 0)]);
+
+        let data = client.transport_mut().data_mut();
+        data.units[3].type_ = UnitType::Unknown as i32;
+        data.units[3].hitPoints = 0;
+        play_frame(&mut client, &[(UnitHide, 3), (MatchFrame, 0)]);
+
+        {
+            let game = client.get_game();
+            let memory = game.get_unit_memory();
+            let barracks = memory.get(3).unwrap();
+            assert_eq!(barracks.type_, UnitType::Terran_Barracks);
+            assert_eq!(barracks.hit_points, 1000);
+            assert_eq!(barracks.last_seen, 1);
+            assert!(!barracks.visible);
+            assert!(barracks.probably_there);
+            assert_eq!(
+                memory
+                    .get_units_in_radius((330, 170), 32, |u: &RememberedUnit| u.player == 1)
+                    .len(),
+                1
+            );
+            assert!(
+                memory
+                    .get_units_in_radius((0, 0), 32, |_: &_| true)
+                    .is_empty()
+            );
+        }
+
+        client.transport_mut().data_mut().isVisible[10][5] = true;
+        play_frame(&mut client, &[(MatchFrame, 0)]);
+        assert!(
+            !client
+                .get_game()
+                .get_unit_memory()
+                .get(3)
+                .unwrap()
+                .probably_there
+        );
+
+        let data = client.transport_mut().data_mut();
+        data.units[3].type_ = UnitType::Terran_Barracks as i32;
+        play_frame(
+            &mut client,
+            &[(UnitShow, 3), (UnitDestroy, 3), (MatchFrame, 0)],
+        );
+        assert!(client.get_game().get_unit_memory().is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+use crate::game::UnitLocation;
+use crate::player::PlayerId;
+use crate::unit::Unit;
+use bwapi_wrapper::prelude::*;
+use core::cell::Ref;
+use rstar::{AABB, RTree};
+use std::collections::HashMap;
+
+/// Positions in the unit list of the current frame, by player and type.
+#[derive(Default)]
+pub(crate) struct UnitIndex {
+    by_player: Vec<Vec<usize>>,
+    by_type: HashMap<UnitType, Vec<usize>>,
+}
+
+impl UnitIndex {
+    /// Number of players owning units.
+    pub(crate) fn player_count(&self) -> usize {
+        self.by_player
+            .iter()
+            .filter(|units| !units.is_empty())
+            .count()
+    }
+
+    /// Keeps the allocations of the previous frame.
+    pub(crate) fn rebuild(&mut self, units: &[Unit]) {
+        self.by_player.iter_mut().for_each(Vec::clear);
+        self.by_type.values_mut().for_each(Vec::clear);
+        for (index, unit) in units.iter().enumerate() {
+            let player = unit.inner.player as usize;
+            if player >= self.by_player.len() {
+                self.by_player.resize_with(player + 1, Vec::new);
+            }
+            self.by_player[player].push(index);
+            self.by_type.entry(unit.get_type()).or_default().push(index);
+        }
+    }
+}
+
+/// The units of the current frame, see `Game::units`. Queries return iterators borrowing from
+/// this view and don't allocate.
+///
+/// The units are updated before each event, so don't keep this view across `Frame::next`.
+pub struct Units<'a> {
+    pub(crate) units: Ref<'a, Vec<Unit>>,
+    pub(crate) rtree: Ref<'a, RTree<UnitLocation>>,
+    pub(crate) index: Ref<'a, UnitIndex>,
+}
+
+impl<'a> Units<'a> {
+    pub fn iter(&self) -> std::slice::Iter<'_, Unit> {
+        self.units.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.units.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+
+    fn indexed<'b>(&'b self, indices: Option<&'b Vec<usize>>) -> impl Iterator<Item = &'b Unit> {
+        indices
+            .into_iter()
+            .flatten()
+            .map(|&index| &self.units[index])
+    }
+
+    pub fn of_player(&self, player: PlayerId) -> impl Iterator<Item = &Unit> {
+        self.indexed(self.index.by_player.get(player))
+    }
+
+    pub fn of_type(&self, type_: UnitType) -> impl Iterator<Item = &Unit> {
+        self.indexed(self.index.by_type.get(&type_))
+    }
+
+    /// Units overlapping the rectangle.
+    pub fn in_rectangle<A: Into<Position>, B: Into<Position>>(
+        &self,
+        lt: A,
+        rb: B,
+    ) -> impl Iterator<Item = &Unit> {
+        let lt = lt.into();
+        let rb = rb.into();
+        self.rtree
+            .locate_in_envelope_intersecting(&AABB::from_corners([lt.x, lt.y], [rb.x, rb.y]))
+            .map(|ul| &self.units[ul.index])
+    }
+
+    /// Units with their center in the circle.
+    pub fn in_radius<P: Into<Position>>(
+        &self,
+        position: P,
+        radius: i32,
+    ) -> impl Iterator<Item = &Unit> {
+        let center = position.into();
+        let radius_sq = (radius * radius) as u32;
+        self.in_rectangle(
+            (center.x - radius, center.y - radius),
+            (center.x + radius, center.y + radius),
+        )
+        .filter(move |u| center.distance_squared(u.get_position()) <= radius_sq)
+    }
+
+    /// Units ordered by the distance of their bounds to `position`.
+    pub fn nearest<P: Into<Position>>(&self, position: P) -> impl Iterator<Item = &Unit> {
+        let center = position.into();
+        self.rtree
+            .nearest_neighbor_iter(&[center.x, center.y])
+            .map(|ul| &self.units[ul.index])
+    }
+}
+
+impl<'b> IntoIterator for &'b Units<'_> {
+    type Item = &'b Unit;
+    type IntoIter = std::slice::Iter<'b, Unit>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aimodule::AiModule;
+    use crate::client::Client;
+    use crate::game::Game;
+    use crate::transport::MemoryTransport;
+    use bwapi_wrapper::prelude::*;
+    use bwapi_wrapper::*;
+
+    struct Idle;
+
+    impl AiModule for Idle {
+        fn on_frame(&mut self, _game: &Game) {}
+    }
+
+    #[test]
+    fn should_query_units_by_player_type_and_position() {
+        let mut client = Client::new(MemoryTransport::default()).unwrap();
+        let data = client.transport_mut().data_mut();
+        data.playerCount = 2;
+        for (id, player, type_, x) in [
+            (1, 0, UnitType::Zerg_Drone, 100),
+            (2, 0, UnitType::Zerg_Larva, 400),
+            (3, 1, UnitType::Zerg_Drone, 120),
+        ] {
+            let unit = &mut data.units[id];
+            unit.exists = true;
+            unit.player = player;
+            unit.type_ = type_ as i32;
+            unit.positionX = x;
+            unit.positionY = 100;
+        }
+        data.frameCount = 1;
+        data.eventCount = 3;
+        for (event, id) in data.events.iter_mut().zip(1..=3) {
+            *event = BWAPIC_Event {
+                type_: BWAPI_EventType_Enum::UnitShow,
+                v1: id,
+                v2: 0,
+            };
+        }
+        client.update(&mut Idle).unwrap();
+
+        let game = client.get_game();
+        let units = game.units();
+        let ids = |units: &mut dyn Iterator<Item = &crate::Unit>| {
+            let mut ids: Vec<_> = units.map(|u| u.get_id()).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(units.len(), 3);
+        assert_eq!(ids(&mut units.of_player(0)), [1, 2]);
+        assert_eq!(ids(&mut units.of_type(UnitType::Zerg_Drone)), [1, 3]);
+        assert!(ids(&mut units.of_type(UnitType::Zerg_Hatchery)).is_empty());
+        assert_eq!(ids(&mut units.in_radius((110, 100), 20)), [1, 3]);
+        assert_eq!(units.nearest((390, 100)).next().unwrap().get_id(), 2);
+        assert_eq!(game.get_player(1).unwrap().get_units().len(), 1);
+    }
+}
@@ -0,0 +1,324 @@
+//! Checks the raw bytes of a `BWAPI_GameData` from an untrusted source, like a recording, a
+//! snapshot file or the network, before they are used as one.
+//!
+//! A `BWAPI_GameData` contains Rust enums and `bool`s, for which most byte patterns are invalid.
+//! Counts are checked as well, so that a corrupt frame is reported as an error instead of
+//! panicking on an out of bounds index later on.
+use bwapi_wrapper::*;
+use num_traits::FromPrimitive;
+use std::io;
+use std::mem::{offset_of, size_of};
+use std::os::raw::c_char;
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Size of a field of `T`, without an instance of `T`
+fn field_size<T, F>(_field: fn(&T) -> &F) -> usize {
+    size_of::<F>()
+}
+
+/// Offsets of all elements of an array field of `T` at `offset`
+fn elements<T, E, const N: usize>(
+    offset: usize,
+    _array: fn(&T) -> &[E; N],
+) -> impl ExactSizeIterator<Item = usize> {
+    (0..N).map(move |i| offset + i * size_of::<E>())
+}
+
+fn read_i32(bytes: &[u8], offset: usize) -> i32 {
+    i32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn check_bools(bytes: &[u8], offset: usize, size: usize, name: &str) -> io::Result<()> {
+    if bytes[offset..offset + size].iter().all(|&b| b <= 1) {
+        Ok(())
+    } else {
+        Err(invalid_data(format!("{} is not a bool", name)))
+    }
+}
+
+fn check_enum<E: FromPrimitive>(bytes: &[u8], offset: usize, name: &str) -> io::Result<()> {
+    debug_assert_eq!(size_of::<E>(), 4);
+    let value = read_i32(bytes, offset);
+    E::from_i32(value)
+        .map(|_| ())
+        .ok_or_else(|| invalid_data(format!("{} has unknown value {}", name, value)))
+}
+
+/// Checks every `bool` field of the `$struct` at `$offset`.
+macro_rules! check_bools {
+    ($bytes:expr, $offset:expr, $struct:ty { $($field:ident),* $(,)? }) => {
+        $(
+            check_bools(
+                $bytes,
+                $offset + offset_of!($struct, $field),
+                field_size(|s: &$struct| &s.$field),
+                stringify!($field),
+            )?;
+        )*
+    };
+}
+
+/// Checks that the count at `$count` is within the length of the array `$array`.
+macro_rules! check_count {
+    ($bytes:expr, $count:ident, $array:ident) => {
+        let len = elements(
+            offset_of!(BWAPI_GameData, $array),
+            |data: &BWAPI_GameData| &data.$array,
+        )
+        .len();
+        let count = read_i32($bytes, offset_of!(BWAPI_GameData, $count));
+        if !(0..=len as i32).contains(&count) {
+            return Err(invalid_data(format!(
+                "{} is out of bounds: {}",
+                stringify!($count),
+                count
+            )));
+        }
+    };
+}
+
+/// Like `check_count`, and returns the offsets of all elements of `$array`.
+macro_rules! array {
+    ($bytes:expr, $count:ident, $array:ident) => {{
+        check_count!($bytes, $count, $array);
+        elements(
+            offset_of!(BWAPI_GameData, $array),
+            |data: &BWAPI_GameData| &data.$array,
+        )
+    }};
+}
+
+/// An entry of the buffers a bot sends its commands to the server with, see `command.rs`.
+pub(crate) trait Entry: Copy {
+    /// Checks the bytes of a single entry.
+    fn check(bytes: &[u8]) -> io::Result<()>;
+}
+
+impl Entry for BWAPIC_Command {
+    fn check(bytes: &[u8]) -> io::Result<()> {
+        check_enum::<BWAPIC_CommandType_Enum>(
+            bytes,
+            offset_of!(BWAPIC_Command, type_),
+            "Command type",
+        )
+    }
+}
+
+impl Entry for BWAPIC_UnitCommand {
+    fn check(bytes: &[u8]) -> io::Result<()> {
+        check_enum::<BWAPI_UnitCommandTypes_Enum_Enum>(
+            bytes,
+            offset_of!(BWAPIC_UnitCommand, type_),
+            "Unit command type",
+        )
+    }
+}
+
+impl Entry for BWAPIC_Shape {
+    fn check(bytes: &[u8]) -> io::Result<()> {
+        check_enum::<BWAPIC_ShapeType_Enum>(bytes, offset_of!(BWAPIC_Shape, type_), "Shape type")?;
+        check_enum::<BWAPI_CoordinateType_Enum>(
+            bytes,
+            offset_of!(BWAPIC_Shape, ctype),
+            "Coordinate type",
+        )?;
+        check_bools!(bytes, 0, BWAPIC_Shape { isSolid });
+        Ok(())
+    }
+}
+
+/// Strings are read as C strings by the server.
+impl<const N: usize> Entry for [c_char; N] {
+    fn check(bytes: &[u8]) -> io::Result<()> {
+        if bytes.contains(&0) {
+            Ok(())
+        } else {
+            Err(invalid_data("String is not terminated".to_string()))
+        }
+    }
+}
+
+fn entry<E: Entry>(bytes: &[u8], offset: usize) -> io::Result<()> {
+    E::check(&bytes[offset..offset + size_of::<E>()])
+}
+
+pub(crate) fn game_data(bytes: &[u8]) -> io::Result<()> {
+    let expected = size_of::<BWAPI_GameData>();
+    if bytes.len() != expected {
+        return Err(invalid_data(format!(
+            "Game data has {} bytes, but BWAPI_GameData needs {}",
+            bytes.len(),
+            expected
+        )));
+    }
+    check_bools!(
+        bytes,
+        0,
+        BWAPI_GameData {
+            isDebug,
+            hasLatCom,
+            hasGUI,
+            mouseState,
+            keyState,
+            flags,
+            isWalkable,
+            isBuildable,
+            isVisible,
+            isExplored,
+            hasCreep,
+            isOccupied,
+            isInGame,
+            isMultiplayer,
+            isBattleNet,
+            isPaused,
+            isReplay,
+        }
+    );
+    check_count!(bytes, forceCount, forces);
+    check_count!(bytes, nukeDotCount, nukeDots);
+    check_count!(bytes, startLocationCount, startLocations);
+    check_count!(bytes, stringCount, strings);
+    for player in array!(bytes, playerCount, players) {
+        check_bools!(
+            bytes,
+            player,
+            BWAPI_PlayerData {
+                isAlly,
+                isEnemy,
+                isNeutral,
+                isVictorious,
+                isDefeated,
+                leftGame,
+                isParticipating,
+                hasResearched,
+                isResearching,
+                isUpgrading,
+                isResearchAvailable,
+                isUnitAvailable,
+            }
+        );
+    }
+    for unit in array!(bytes, initialUnitCount, units) {
+        check_bools!(
+            bytes,
+            unit,
+            BWAPI_UnitData {
+                exists,
+                hasNuke,
+                isAccelerating,
+                isAttacking,
+                isAttackFrame,
+                isBeingGathered,
+                isBlind,
+                isBraking,
+                isBurrowed,
+                isCloaked,
+                isCompleted,
+                isConstructing,
+                isDetected,
+                isGathering,
+                isHallucination,
+                isIdle,
+                isInterruptible,
+                isInvincible,
+                isLifted,
+                isMorphing,
+                isMoving,
+                isParasited,
+                isSelected,
+                isStartingAttack,
+                isStuck,
+                isTraining,
+                isUnderStorm,
+                isUnderDarkSwarm,
+                isUnderDWeb,
+                isPowered,
+                isVisible,
+                recentlyAttacked,
+            }
+        );
+    }
+    for bullet in elements(
+        offset_of!(BWAPI_GameData, bullets),
+        |data: &BWAPI_GameData| &data.bullets,
+    ) {
+        check_bools!(bytes, bullet, BWAPI_BulletData { exists, isVisible });
+    }
+    for region in array!(bytes, regionCount, regions) {
+        check_bools!(
+            bytes,
+            region,
+            BWAPI_RegionData {
+                isAccessible,
+                isHigherGround
+            }
+        );
+    }
+    for event in array!(bytes, eventCount, events) {
+        check_enum::<BWAPI_EventType_Enum>(
+            bytes,
+            event + offset_of!(BWAPIC_Event, type_),
+            "Event type",
+        )?;
+    }
+    for command in array!(bytes, commandCount, commands) {
+        entry::<BWAPIC_Command>(bytes, command)?;
+    }
+    for command in array!(bytes, unitCommandCount, unitCommands) {
+        entry::<BWAPIC_UnitCommand>(bytes, command)?;
+    }
+    for shape in array!(bytes, shapeCount, shapes) {
+        entry::<BWAPIC_Shape>(bytes, shape)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shm::Shm;
+
+    fn bytes_of(data: &BWAPI_GameData) -> Vec<u8> {
+        unsafe {
+            std::slice::from_raw_parts(
+                data as *const BWAPI_GameData as *const u8,
+                size_of::<BWAPI_GameData>(),
+            )
+        }
+        .to_vec()
+    }
+
+    #[test]
+    fn should_accept_valid_game_data() {
+        let mut data: Shm<BWAPI_GameData> = Shm::zeroed();
+        data.eventCount = 1;
+        data.events[0].type_ = BWAPI_EventType_Enum::MatchFrame;
+        data.units[0].exists = true;
+
+        assert!(game_data(&bytes_of(&data)).is_ok());
+    }
+
+    #[test]
+    fn should_reject_invalid_bools_enums_and_counts() {
+        let data: Shm<BWAPI_GameData> = Shm::zeroed();
+        let valid = bytes_of(&data);
+
+        let mut bools = valid.clone();
+        bools[offset_of!(BWAPI_GameData, units) + offset_of!(BWAPI_UnitData, exists)] = 2;
+        let mut events = valid.clone();
+        events[offset_of!(BWAPI_GameData, events)..][..4].copy_from_slice(&(-1i32).to_ne_bytes());
+        let mut counts = valid.clone();
+        counts[offset_of!(BWAPI_GameData, eventCount)..][..4]
+            .copy_from_slice(&i32::MAX.to_ne_bytes());
+
+        for invalid in [bools, events, counts, valid[1..].to_vec()] {
+            assert_eq!(
+                game_data(&invalid).unwrap_err().kind(),
+                io::ErrorKind::InvalidData
+            );
+        }
+    }
+}
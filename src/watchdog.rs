@@ -0,0 +1,185 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// At most `max_frames` frames of a game may take `frame_time` or longer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameTimeLimit {
+    pub frame_time: Duration,
+    pub max_frames: u32,
+}
+
+/// Tracks the time the bot needs per frame against the limits of a tournament.
+///
+/// Set it with `Client::set_watchdog`, then use `Game::get_remaining_frame_budget` to defer
+/// expensive work and `Game::get_watchdog` in `on_end` to see the violations of the game.
+#[derive(Clone, Debug)]
+pub struct Watchdog {
+    limits: Vec<FrameTimeLimit>,
+    /// Frames which exceeded the limit with the same index
+    exceeded: Vec<u32>,
+    frame_start: Option<Instant>,
+    frames: u32,
+    longest_frame: Duration,
+}
+
+impl Watchdog {
+    pub fn new(limits: Vec<FrameTimeLimit>) -> Self {
+        Self {
+            exceeded: vec![0; limits.len()],
+            limits,
+            frame_start: None,
+            frames: 0,
+            longest_frame: Duration::ZERO,
+        }
+    }
+
+    /// The rules of SSCAIT and BASIL: 320 frames of 55ms, 10 frames of 1s and no frame of 10s.
+    pub fn sscait() -> Self {
+        Self::new(vec![
+            FrameTimeLimit {
+                frame_time: Duration::from_millis(55),
+                max_frames: 320,
+            },
+            FrameTimeLimit {
+                frame_time: Duration::from_secs(1),
+                max_frames: 10,
+            },
+            FrameTimeLimit {
+                frame_time: Duration::from_secs(10),
+                max_frames: 0,
+            },
+        ])
+    }
+
+    /// Time a frame may take without counting against any limit.
+    pub fn get_budget(&self) -> Duration {
+        self.limits
+            .iter()
+            .map(|limit| limit.frame_time)
+            .min()
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// Time left of the budget in the current frame.
+    pub fn get_remaining(&self) -> Duration {
+        let elapsed = self
+            .frame_start
+            .map(|start| start.elapsed())
+            .unwrap_or_default();
+        self.get_budget().saturating_sub(elapsed)
+    }
+
+    /// Each limit with the number of frames that exceeded it.
+    pub fn get_violations(&self) -> impl Iterator<Item = (FrameTimeLimit, u32)> + '_ {
+        self.limits
+            .iter()
+            .copied()
+            .zip(self.exceeded.iter().copied())
+    }
+
+    /// Whether a limit was exceeded more often than allowed.
+    pub fn is_disqualified(&self) -> bool {
+        self.get_violations()
+            .any(|(limit, frames)| frames > limit.max_frames)
+    }
+
+    pub fn get_frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn get_longest_frame(&self) -> Duration {
+        self.longest_frame
+    }
+
+    /// Forgets the frames of the previous game.
+    pub(crate) fn reset(&mut self) {
+        self.exceeded.fill(0);
+        self.frames = 0;
+        self.longest_frame = Duration::ZERO;
+    }
+
+    pub(crate) fn start_frame(&mut self) {
+        self.frame_start = Some(Instant::now());
+    }
+
+    pub(crate) fn end_frame(&mut self) {
+        if let Some(start) = self.frame_start.take() {
+            self.record(start.elapsed());
+        }
+    }
+
+    fn record(&mut self, frame_time: Duration) {
+        self.frames += 1;
+        self.longest_frame = self.longest_frame.max(frame_time);
+        for (limit, exceeded) in self.limits.iter().zip(self.exceeded.iter_mut()) {
+            if frame_time >= limit.frame_time {
+                *exceeded += 1;
+                if *exceeded == limit.max_frames + 1 {
+                    println!(
+                        "Exceeded the frame time limit: {} frames took {:?} or longer",
+                        exceeded, limit.frame_time
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Watchdog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} frames, longest {:?}",
+            self.frames, self.longest_frame
+        )?;
+        for (limit, frames) in self.get_violations() {
+            write!(
+                f,
+                ", {}/{} frames >= {:?}",
+                frames, limit.max_frames, limit.frame_time
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_count_frames_exceeding_limits() {
+        let mut watchdog = Watchdog::sscait();
+        for _ in 0..320 {
+            watchdog.record(Duration::from_millis(60));
+        }
+        watchdog.record(Duration::from_millis(1500));
+        watchdog.record(Duration::from_millis(10));
+
+        assert_eq!(
+            watchdog
+                .get_violations()
+                .map(|(_, frames)| frames)
+                .collect::<Vec<_>>(),
+            [321, 1, 0]
+        );
+        assert!(watchdog.is_disqualified());
+        assert_eq!(watchdog.get_longest_frame(), Duration::from_millis(1500));
+
+        watchdog.reset();
+        assert!(!watchdog.is_disqualified());
+        assert_eq!(watchdog.get_frames(), 0);
+    }
+
+    #[test]
+    fn should_report_remaining_budget() {
+        let mut watchdog = Watchdog::sscait();
+        assert_eq!(watchdog.get_remaining(), Duration::from_millis(55));
+
+        watchdog.start_frame();
+        assert!(watchdog.get_remaining() <= Duration::from_millis(55));
+        watchdog.end_frame();
+
+        assert_eq!(watchdog.get_frames(), 1);
+    }
+}